@@ -1,4 +1,130 @@
-pub fn encrypt(plaintext: &[u8], key: &[u8; 32]) -> Vec<u8> {
+use crate::secret::Secret;
+
+pub const NONCE_LEN: usize = 12;
+pub const TAG_LEN: usize = 16;
+
+/// Returned when a cyphertext fails authentication
+#[derive(Debug)]
+pub struct TagMismatch;
+
+/// AES-256-GCM with a random nonce, returns `nonce || cyphertext || tag`
+pub fn encrypt(plaintext: &[u8], key: &[u8; 32], aad: &[u8]) -> Vec<u8> {
+    let nonce = rand::random::<[u8; NONCE_LEN]>();
+
+    let mut cyphertext = nonce.to_vec();
+    cyphertext.extend_from_slice(&seal(plaintext, key, &nonce, aad));
+
+    cyphertext
+}
+
+/// Inverse of [`encrypt`], fails if the cyphertext or `aad` have been tampered with
+pub fn decrypt(
+    cyphertext: &[u8],
+    key: &[u8; 32],
+    aad: &[u8],
+) -> Result<Secret<Vec<u8>>, TagMismatch> {
+    let Some((nonce, cyphertext)) = cyphertext.split_first_chunk::<NONCE_LEN>() else {
+        return Err(TagMismatch);
+    };
+
+    open(cyphertext, key, nonce, aad)
+}
+
+fn seal(plaintext: &[u8], key: &[u8; 32], nonce: &[u8; NONCE_LEN], aad: &[u8]) -> Vec<u8> {
+    let mut cyphertext = plaintext.to_vec();
+    ctr(&mut cyphertext, key, nonce);
+
+    let tag = tag(&cyphertext, key, nonce, aad);
+    cyphertext.extend_from_slice(&tag);
+
+    cyphertext
+}
+
+fn open(
+    cyphertext: &[u8],
+    key: &[u8; 32],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+) -> Result<Secret<Vec<u8>>, TagMismatch> {
+    let Some((cyphertext, expected)) = cyphertext.split_last_chunk::<TAG_LEN>() else {
+        return Err(TagMismatch);
+    };
+
+    let tag = tag(cyphertext, key, nonce, aad);
+
+    // compare without exiting early on the first mismatch
+    let difference = tag
+        .iter()
+        .zip(expected)
+        .fold(0, |acc, (l, r)| acc | (l ^ r));
+    if difference != 0 {
+        return Err(TagMismatch);
+    }
+
+    let mut plaintext = Secret::new(cyphertext.to_vec());
+    ctr(&mut plaintext, key, nonce);
+
+    Ok(plaintext)
+}
+
+/// Counter mode keystream, starting from the block after `J0`
+fn ctr(data: &mut [u8], key: &[u8; 32], nonce: &[u8; NONCE_LEN]) {
+    for (i, chunk) in data.chunks_mut(16).enumerate() {
+        let mut block = Secret::new(counter_block(nonce, i as u32 + 2));
+        encrypt_block(&mut block, key);
+
+        for (byte, keystream) in chunk.iter_mut().zip(block.iter()) {
+            *byte ^= keystream;
+        }
+    }
+}
+
+fn tag(cyphertext: &[u8], key: &[u8; 32], nonce: &[u8; NONCE_LEN], aad: &[u8]) -> [u8; TAG_LEN] {
+    let mut h = [0; 16];
+    encrypt_block(&mut h, key);
+    let h = u128::from_be_bytes(h);
+
+    let mut ghash = 0;
+    for chunk in aad.chunks(16).chain(cyphertext.chunks(16)) {
+        let mut block = [0; 16];
+        block[..chunk.len()].copy_from_slice(chunk);
+        ghash = gf_mul(ghash ^ u128::from_be_bytes(block), h);
+    }
+
+    let lengths = ((aad.len() as u128 * 8) << 64) | (cyphertext.len() as u128 * 8);
+    ghash = gf_mul(ghash ^ lengths, h);
+
+    let mut j0 = counter_block(nonce, 1);
+    encrypt_block(&mut j0, key);
+
+    (ghash ^ u128::from_be_bytes(j0)).to_be_bytes()
+}
+
+fn counter_block(nonce: &[u8; NONCE_LEN], counter: u32) -> [u8; 16] {
+    let mut block = [0; 16];
+    block[..NONCE_LEN].copy_from_slice(nonce);
+    block[NONCE_LEN..].copy_from_slice(&counter.to_be_bytes());
+
+    block
+}
+
+/// Multiplication in GF(2^128) as defined by GCM, without data dependent branches
+fn gf_mul(x: u128, y: u128) -> u128 {
+    let mut z = 0;
+    let mut v = y;
+
+    for i in 0..128 {
+        let bit = (x >> (127 - i)) & 1;
+        z ^= v & 0_u128.wrapping_sub(bit);
+        v = (v >> 1) ^ ((0xE1 << 120) & 0_u128.wrapping_sub(v & 1));
+    }
+
+    z
+}
+
+/// Legacy AES-256-ECB with PKCS#7 padding, only used to write vaults from older versions in tests
+#[cfg(test)]
+pub fn encrypt_ecb(plaintext: &[u8], key: &[u8; 32]) -> Vec<u8> {
     let mut cyphertext = plaintext.to_vec();
 
     let padding_start = cyphertext.len() / 16;
@@ -25,23 +151,33 @@ pub fn encrypt(plaintext: &[u8], key: &[u8; 32]) -> Vec<u8> {
     cyphertext
 }
 
-pub fn decrypt(cyphertext: &[u8], key: &[u8; 32]) -> Vec<u8> {
-    let mut plaintext = cyphertext.to_vec();
+/// Legacy AES-256-ECB, kept to migrate vaults written by older versions
+pub fn decrypt_ecb(cyphertext: &[u8], key: &[u8; 32]) -> Option<Secret<Vec<u8>>> {
+    if cyphertext.is_empty() || !cyphertext.len().is_multiple_of(16) {
+        return None;
+    }
+
+    let mut plaintext = Secret::new(cyphertext.to_vec());
 
     for block in plaintext.as_chunks_mut::<16>().0 {
         decrypt_block(block, key);
     }
 
     let len = cyphertext.len();
-    let padding = *plaintext.last().unwrap() as usize;
+    let padding = *plaintext.last()? as usize;
+    if padding == 0 || padding > 16 {
+        return None;
+    }
+
     plaintext.drain(len - padding..);
 
-    plaintext
+    Some(plaintext)
 }
 
 fn encrypt_block(block: &mut [u8; 16], key: &[u8; 32]) {
     let key = unsafe { std::mem::transmute(key) };
-    let expanded_key: [u8; 512] = unsafe { std::mem::transmute(expand_key(key)) };
+    let expanded_key: Secret<[u8; 512]> =
+        Secret::new(unsafe { std::mem::transmute::<[u32; 128], [u8; 512]>(expand_key(key)) });
 
     add_round_key(0, block, &expanded_key);
     for round in 1..14 {
@@ -58,7 +194,8 @@ fn encrypt_block(block: &mut [u8; 16], key: &[u8; 32]) {
 
 fn decrypt_block(block: &mut [u8; 16], key: &[u8; 32]) {
     let key = unsafe { std::mem::transmute(key) };
-    let expanded_key: [u8; 512] = unsafe { std::mem::transmute(expand_key(key)) };
+    let expanded_key: Secret<[u8; 512]> =
+        Secret::new(unsafe { std::mem::transmute::<[u32; 128], [u8; 512]>(expand_key(key)) });
 
     add_round_key(14, block, &expanded_key);
     inv_shift_rows(block);
@@ -333,9 +470,12 @@ fn test() {
     let mut key = [3; 32];
     for i in 0..u16::MAX {
         let plaintext = ((i as usize) << 16).to_le_bytes();
-        let cyphertext = encrypt(&plaintext, &key);
+        let cyphertext = encrypt_ecb(&plaintext, &key);
 
-        assert_eq!(plaintext, decrypt(&cyphertext, &key).as_slice());
+        assert_eq!(
+            plaintext,
+            decrypt_ecb(&cyphertext, &key).unwrap().as_slice()
+        );
 
         key[0] ^= cyphertext[0];
         key[1] ^= cyphertext[1];
@@ -348,7 +488,7 @@ fn test() {
     }
 
     assert_eq!(
-        encrypt(b"Hello, World!", b"0123456789ABCDEF0123456789ABCDEF"),
+        encrypt_ecb(b"Hello, World!", b"0123456789ABCDEF0123456789ABCDEF"),
         &[
             0xDF, 0x51, 0xEC, 0x89, 0x54, 0x82, 0x7F, 0xF6, 0xFE, 0x0C, 0xDA, 0x77, 0x7F, 0xE9,
             0x98, 0x42
@@ -356,13 +496,87 @@ fn test() {
     );
 
     assert_eq!(
-        decrypt(
+        decrypt_ecb(
             &[
                 0xDF, 0x51, 0xEC, 0x89, 0x54, 0x82, 0x7F, 0xF6, 0xFE, 0x0C, 0xDA, 0x77, 0x7F, 0xE9,
                 0x98, 0x42
             ],
             b"0123456789ABCDEF0123456789ABCDEF"
-        ),
+        )
+        .unwrap()
+        .as_slice(),
+        b"Hello, World!"
+    );
+}
+
+#[test]
+fn test_gcm() {
+    // test cases 13, 14 and 16 from the GCM specification
+    let key = [0; 32];
+    let nonce = [0; NONCE_LEN];
+    assert_eq!(
+        seal(&[], &key, &nonce, &[]),
+        [
+            0x53, 0x0f, 0x8a, 0xfb, 0xc7, 0x45, 0x36, 0xb9, 0xa9, 0x63, 0xb4, 0xf1, 0xc4, 0xcb,
+            0x73, 0x8b
+        ]
+    );
+
+    assert_eq!(
+        seal(&[0; 16], &key, &nonce, &[]),
+        [
+            0xce, 0xa7, 0x40, 0x3d, 0x4d, 0x60, 0x6b, 0x6e, 0x07, 0x4e, 0xc5, 0xd3, 0xba, 0xf3,
+            0x9d, 0x18, 0xd0, 0xd1, 0xc8, 0xa7, 0x99, 0x99, 0x6b, 0xf0, 0x26, 0x5b, 0x98, 0xb5,
+            0xd4, 0x8a, 0xb9, 0x19
+        ]
+    );
+
+    let key = [
+        0xfe, 0xff, 0xe9, 0x92, 0x86, 0x65, 0x73, 0x1c, 0x6d, 0x6a, 0x8f, 0x94, 0x67, 0x30, 0x83,
+        0x08, 0xfe, 0xff, 0xe9, 0x92, 0x86, 0x65, 0x73, 0x1c, 0x6d, 0x6a, 0x8f, 0x94, 0x67, 0x30,
+        0x83, 0x08,
+    ];
+    let nonce = [
+        0xca, 0xfe, 0xba, 0xbe, 0xfa, 0xce, 0xdb, 0xad, 0xde, 0xca, 0xf8, 0x88,
+    ];
+    let plaintext = [
+        0xd9, 0x31, 0x32, 0x25, 0xf8, 0x84, 0x06, 0xe5, 0xa5, 0x59, 0x09, 0xc5, 0xaf, 0xf5, 0x26,
+        0x9a, 0x86, 0xa7, 0xa9, 0x53, 0x15, 0x34, 0xf7, 0xda, 0x2e, 0x4c, 0x30, 0x3d, 0x8a, 0x31,
+        0x8a, 0x72, 0x1c, 0x3c, 0x0c, 0x95, 0x95, 0x68, 0x09, 0x53, 0x2f, 0xcf, 0x0e, 0x24, 0x49,
+        0xa6, 0xb5, 0x25, 0xb1, 0x6a, 0xed, 0xf5, 0xaa, 0x0d, 0xe6, 0x57, 0xba, 0x63, 0x7b, 0x39,
+    ];
+    let aad = [
+        0xfe, 0xed, 0xfa, 0xce, 0xde, 0xad, 0xbe, 0xef, 0xfe, 0xed, 0xfa, 0xce, 0xde, 0xad, 0xbe,
+        0xef, 0xab, 0xad, 0xda, 0xd2,
+    ];
+    let cyphertext = seal(&plaintext, &key, &nonce, &aad);
+    assert_eq!(
+        cyphertext,
+        [
+            0x52, 0x2d, 0xc1, 0xf0, 0x99, 0x56, 0x7d, 0x07, 0xf4, 0x7f, 0x37, 0xa3, 0x2a, 0x84,
+            0x42, 0x7d, 0x64, 0x3a, 0x8c, 0xdc, 0xbf, 0xe5, 0xc0, 0xc9, 0x75, 0x98, 0xa2, 0xbd,
+            0x25, 0x55, 0xd1, 0xaa, 0x8c, 0xb0, 0x8e, 0x48, 0x59, 0x0d, 0xbb, 0x3d, 0xa7, 0xb0,
+            0x8b, 0x10, 0x56, 0x82, 0x88, 0x38, 0xc5, 0xf6, 0x1e, 0x63, 0x93, 0xba, 0x7a, 0x0a,
+            0xbc, 0xc9, 0xf6, 0x62, 0x76, 0xfc, 0x6e, 0xce, 0x0f, 0x4e, 0x17, 0x68, 0xcd, 0xdf,
+            0x88, 0x53, 0xbb, 0x2d, 0x55, 0x1b
+        ]
+    );
+    assert_eq!(*open(&cyphertext, &key, &nonce, &aad).unwrap(), plaintext);
+
+    // any modification of the cyphertext, tag or associated data must be detected
+    let cyphertext = encrypt(b"Hello, World!", &key, b"name");
+    assert_eq!(
+        decrypt(&cyphertext, &key, b"name").unwrap().as_slice(),
         b"Hello, World!"
     );
+    assert!(decrypt(&cyphertext, &key, b"other").is_err());
+    for i in 0..cyphertext.len() {
+        let mut tampered = cyphertext.clone();
+        tampered[i] ^= 1;
+        assert!(decrypt(&tampered, &key, b"name").is_err());
+    }
+    assert!(decrypt(&cyphertext[..NONCE_LEN + TAG_LEN - 1], &key, b"name").is_err());
+
+    // identical plaintexts must not produce identical cyphertexts
+    assert_ne!(encrypt(b"secret", &key, &[]), encrypt(b"secret", &key, &[]));
 }
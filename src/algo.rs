@@ -35,25 +35,146 @@ fn alphabetical(lhs: &str, rhs: &str) -> bool {
     }
 }
 
-/// Binary Search
-pub fn contains(name: &str, list: &[Password]) -> bool {
-    let mut lhs = 0;
-    let mut rhs = list.len();
+/// Indices of the entries whose name or account match `query`, best match first
+///
+/// Matching ignores case, and ranks exact matches above prefixes, prefixes above
+/// substrings and substrings above fuzzy matches, where the characters of `query` appear
+/// in order but not next to each other. Within the same kind of match, name matches rank
+/// above account matches, and fuzzy matches are then ordered by their score.
+pub fn search(query: &str, list: &[Password]) -> Vec<usize> {
+    let query = query.to_lowercase();
+    if query.is_empty() {
+        return (0..list.len()).collect();
+    }
 
-    loop {
-        let middle = (rhs - lhs) / 2 + lhs;
-        if middle == lhs {
-            return false;
-        }
+    let mut results: Vec<(Rank, usize)> = list
+        .iter()
+        .enumerate()
+        .filter_map(|(i, password)| {
+            let name = score(&query, &password.name).map(|(tier, fuzzy)| (tier, true, fuzzy));
+            let account =
+                score(&query, &password.account).map(|(tier, fuzzy)| (tier, false, fuzzy));
+            Some((name.max(account)?, i))
+        })
+        .collect();
 
-        let element = &list[middle];
+    // stable, so equal ranks keep alphabetical order
+    results.sort_by_key(|&(rank, _)| std::cmp::Reverse(rank));
+    results.into_iter().map(|(_, i)| i).collect()
+}
 
-        if name == element.name {
-            return true;
-        } else if alphabetical(name, &element.name) {
-            lhs = middle;
-        } else {
-            rhs = middle;
+/// Kind of match, worst first
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum Tier {
+    Fuzzy,
+    Substring,
+    Prefix,
+    Exact,
+}
+
+/// Rank of an entry, compared in order: the kind of match, whether it was the name,
+/// and the fuzzy score
+type Rank = (Tier, bool, u32);
+
+/// How well `text` matches the lowercase `query`, the fuzzy score is 0 unless the
+/// match is fuzzy
+fn score(query: &str, text: &str) -> Option<(Tier, u32)> {
+    let text = text.to_lowercase();
+
+    if text == query {
+        Some((Tier::Exact, 0))
+    } else if text.starts_with(query) {
+        Some((Tier::Prefix, 0))
+    } else if text.contains(query) {
+        Some((Tier::Substring, 0))
+    } else {
+        fuzzy(query, &text).map(|score| (Tier::Fuzzy, score))
+    }
+}
+
+/// Match the characters of `query` in order, rewarding runs of consecutive characters
+/// and penalising gaps, so "gml" ranks "gmail" above "google mail"
+fn fuzzy(query: &str, text: &str) -> Option<u32> {
+    let mut text = text.chars();
+    let mut score: u32 = 1000;
+    let mut consecutive = false;
+
+    for q in query.chars() {
+        let mut gap = 0;
+        loop {
+            let t = text.next()?;
+            if t == q {
+                break;
+            }
+            gap += 1;
+        }
+
+        if consecutive && gap == 0 {
+            score += 10;
         }
+        score = score.saturating_sub(gap);
+        consecutive = true;
     }
+
+    Some(score.max(1))
+}
+
+#[test]
+fn test() {
+    let mut list = ["mail", "bank", "work", "alpha"]
+        .map(|name| Password {
+            name: name.to_string(),
+            ..Password::default()
+        })
+        .to_vec();
+    sort(&mut list);
+
+    let names = list.iter().map(|password| password.name.as_str());
+    assert!(names.eq(["alpha", "bank", "mail", "work"]));
+}
+
+#[test]
+fn test_search() {
+    let list: Vec<Password> = [
+        ("Bank", "me@example.com"),
+        ("Gmail", "personal"),
+        ("Google Mail", "work"),
+        ("Mail", "me@example.com"),
+        ("Work", "gmail"),
+    ]
+    .map(|(name, account)| Password {
+        name: name.to_string(),
+        account: account.to_string(),
+        ..Password::default()
+    })
+    .to_vec();
+
+    let names = |query| -> Vec<&str> {
+        search(query, &list)
+            .into_iter()
+            .map(|i| list[i].name.as_str())
+            .collect()
+    };
+
+    assert_eq!(names(""), ["Bank", "Gmail", "Google Mail", "Mail", "Work"]);
+    assert_eq!(names("MAIL"), ["Mail", "Gmail", "Google Mail", "Work"]);
+    assert_eq!(names("gml"), ["Gmail", "Google Mail", "Work"]);
+    assert_eq!(names("example"), ["Bank", "Mail"]);
+
+    assert!(names("xyz").is_empty());
+
+    // an exact account beats a name that only contains the query, and a name beats an
+    // account matched the same way
+    let list: Vec<Password> = [("Gmail", "personal"), ("Shop", "mail"), ("Work", "gmail")]
+        .map(|(name, account)| Password {
+            name: name.to_string(),
+            account: account.to_string(),
+            ..Password::default()
+        })
+        .to_vec();
+    let ranked: Vec<&str> = search("mail", &list)
+        .into_iter()
+        .map(|i| list[i].name.as_str())
+        .collect();
+    assert_eq!(ranked, ["Shop", "Gmail", "Work"]);
 }
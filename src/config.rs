@@ -0,0 +1,114 @@
+//! Settings that can't live in a vault because they are needed before one is open,
+//! kept in a small file in the user's configuration directory
+
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+
+/// Number of vaults remembered
+const RECENT_LEN: usize = 8;
+
+/// Vaults opened recently, most recent first, stored one path per line
+#[derive(Default)]
+pub struct Recent {
+    // `None` if there is no configuration directory, the list is then forgotten on exit
+    file: Option<PathBuf>,
+    pub vaults: Vec<PathBuf>,
+}
+
+impl Recent {
+    pub fn load() -> Self {
+        Self::load_from(config_dir().map(|dir| dir.join("goonpass").join("recent")))
+    }
+
+    fn load_from(file: Option<PathBuf>) -> Self {
+        // a missing or unreadable file is an empty list, and vaults that were
+        // moved or deleted since are dropped
+        let vaults = file
+            .as_ref()
+            .and_then(|file| std::fs::read_to_string(file).ok())
+            .unwrap_or_default()
+            .lines()
+            .map(PathBuf::from)
+            .filter(|vault| vault.is_file())
+            .take(RECENT_LEN)
+            .collect();
+
+        Self { file, vaults }
+    }
+
+    /// Move `vault` to the top of the list and save it
+    pub fn remember(&mut self, vault: &Path) -> Result<(), Error> {
+        let vault = std::fs::canonicalize(vault)?;
+
+        self.vaults.retain(|recent| *recent != vault);
+        self.vaults.insert(0, vault);
+        self.vaults.truncate(RECENT_LEN);
+
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let mut contents = String::new();
+        for vault in &self.vaults {
+            // a path that can't be written as a line is only remembered until exit
+            if let Some(vault) = vault.to_str()
+                && !vault.contains('\n')
+            {
+                contents.push_str(vault);
+                contents.push('\n');
+            }
+        }
+        std::fs::write(file, contents)?;
+
+        Ok(())
+    }
+}
+
+/// `%APPDATA%` on Windows, `$XDG_CONFIG_HOME` or `~/.config` elsewhere
+fn config_dir() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+
+    if cfg!(windows) {
+        var("APPDATA").map(PathBuf::from)
+    } else {
+        var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".config")))
+    }
+}
+
+#[test]
+fn test() {
+    let dir = crate::TempPath::new();
+    let file = dir.join("config").join("recent");
+    std::fs::create_dir(&dir).unwrap();
+
+    let vaults: Vec<_> = ["a", "b", "c"].iter().map(|name| dir.join(name)).collect();
+    for vault in &vaults {
+        std::fs::write(vault, "").unwrap();
+    }
+
+    let mut recent = Recent::load_from(Some(file.clone()));
+    assert!(recent.vaults.is_empty());
+    for vault in &vaults {
+        recent.remember(vault).unwrap();
+    }
+    // opening a vault again moves it to the top
+    recent.remember(&vaults[0]).unwrap();
+
+    let canonical = |index: usize| std::fs::canonicalize(&vaults[index]).unwrap();
+    let expected = [canonical(0), canonical(2), canonical(1)];
+    assert_eq!(Recent::load_from(Some(file.clone())).vaults, expected);
+
+    // deleted vaults are dropped
+    std::fs::remove_file(&vaults[2]).unwrap();
+    assert_eq!(
+        Recent::load_from(Some(file)).vaults,
+        [canonical(0), canonical(1)]
+    );
+}
@@ -0,0 +1,93 @@
+use crate::{aes, sqlite};
+
+#[derive(Debug)]
+pub enum Error {
+    /// The master password does not match the stored verifier
+    WrongPassword,
+    /// The master password and key file together do not match the stored verifier
+    WrongPasswordOrKeyFile,
+    /// The vault was created with a key file, but none was given
+    KeyFileRequired,
+    /// A key file was given, but the vault doesn't use one
+    KeyFileNotUsed,
+    /// A key file can only be generated when creating a vault
+    VaultExists,
+    /// There is no vault to recover
+    VaultNotFound,
+    /// The recovery code does not match, or the vault has none
+    WrongRecoveryCode,
+    /// The database is readable but its contents are not a valid vault
+    Corrupted(&'static str),
+    /// The vault was written by a newer version of the app
+    UnsupportedFormat(u32),
+    Sqlite(sqlite::Error),
+    /// An entry failed authentication
+    Decryption,
+    /// An entry with this name already exists
+    Duplicate(String),
+    /// The authenticator secret is neither base32 nor an `otpauth://totp/` URI
+    InvalidTotp,
+    Io(std::io::Error),
+    /// SQLite only opens paths that are valid UTF-8
+    InvalidPath(std::path::PathBuf),
+    Clipboard(arboard::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WrongPassword => write!(f, "Wrong master password"),
+            Self::WrongPasswordOrKeyFile => write!(f, "Wrong master password or key file"),
+            Self::KeyFileRequired => write!(f, "This vault needs its key file"),
+            Self::KeyFileNotUsed => write!(f, "This vault doesn't use a key file"),
+            Self::VaultExists => write!(f, "The vault already exists, choose its key file instead"),
+            Self::VaultNotFound => write!(f, "The vault doesn't exist"),
+            Self::WrongRecoveryCode => write!(f, "Wrong recovery code"),
+            Self::Corrupted(reason) => write!(f, "The vault is corrupted: {reason}"),
+            Self::UnsupportedFormat(format) => {
+                write!(
+                    f,
+                    "The vault uses format {format}, which is newer than this app"
+                )
+            }
+            Self::Sqlite(error) => write!(f, "Database error: {error}"),
+            Self::Decryption => write!(f, "The entry could not be decrypted"),
+            Self::Duplicate(name) => write!(f, "An entry named \"{name}\" already exists"),
+            Self::InvalidTotp => write!(
+                f,
+                "The authenticator secret must be base32 or an otpauth://totp/ link"
+            ),
+            Self::Io(error) => write!(f, "{error}"),
+            Self::InvalidPath(path) => {
+                write!(f, "The path {} isn't valid Unicode", path.display())
+            }
+            Self::Clipboard(error) => write!(f, "Clipboard error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<aes::TagMismatch> for Error {
+    fn from(_: aes::TagMismatch) -> Self {
+        Self::Decryption
+    }
+}
+
+impl From<sqlite::Error> for Error {
+    fn from(error: sqlite::Error) -> Self {
+        Self::Sqlite(error)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<arboard::Error> for Error {
+    fn from(error: arboard::Error) -> Self {
+        Self::Clipboard(error)
+    }
+}
@@ -0,0 +1,194 @@
+//! Random passwords and diceware-style passphrases
+
+use rand::seq::SliceRandom;
+
+use crate::secret::Secret;
+
+/// Embedded wordlist for passphrases, one word per line (the BIP39 English list)
+const WORDS: &str = include_str!("../assets/words.txt");
+const WORD_COUNT: usize = 2048;
+
+/// Characters that are easily confused with each other in many fonts
+const AMBIGUOUS: &str = "Il1|O0o";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Class {
+    Lowercase,
+    Uppercase,
+    Digits,
+    Symbols,
+}
+
+impl Class {
+    pub const ALL: [Class; 4] = [
+        Class::Lowercase,
+        Class::Uppercase,
+        Class::Digits,
+        Class::Symbols,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Class::Lowercase => "a-z",
+            Class::Uppercase => "A-Z",
+            Class::Digits => "0-9",
+            Class::Symbols => "!@#",
+        }
+    }
+
+    fn characters(self, exclude_ambiguous: bool) -> Vec<char> {
+        let characters = match self {
+            Class::Lowercase => "abcdefghijklmnopqrstuvwxyz",
+            Class::Uppercase => "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+            Class::Digits => "0123456789",
+            Class::Symbols => "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~",
+        };
+
+        characters
+            .chars()
+            .filter(|c| !exclude_ambiguous || !AMBIGUOUS.contains(*c))
+            .collect()
+    }
+}
+
+pub struct Generator {
+    /// Generate a passphrase instead of a password
+    pub passphrase: bool,
+    pub length: usize,
+    /// Minimum count of each class in [`Class::ALL`] order, `None` leaves the class out
+    pub classes: [Option<usize>; 4],
+    pub exclude_ambiguous: bool,
+    /// Number of words in a passphrase
+    pub words: usize,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Self {
+            passphrase: false,
+            length: 20,
+            classes: [Some(1); 4],
+            exclude_ambiguous: true,
+            words: 4,
+        }
+    }
+}
+
+impl Generator {
+    /// Generate a secret with the current settings, `None` if they can't be satisfied
+    pub fn generate(&self) -> Option<Secret<String>> {
+        if self.passphrase {
+            return self.generate_passphrase();
+        }
+
+        let classes = self.enabled();
+        let minimum: usize = classes.iter().map(|(_, minimum)| minimum).sum();
+        if classes.is_empty() || minimum > self.length {
+            return None;
+        }
+
+        // the minimum of every class first, the rest from all of them, then shuffle
+        let mut password = Secret::new(Vec::with_capacity(self.length));
+        for (characters, minimum) in &classes {
+            password.extend((0..*minimum).map(|_| choose(characters)));
+        }
+
+        let pool: Vec<char> = classes.into_iter().flat_map(|(c, _)| c).collect();
+        password.extend((minimum..self.length).map(|_| choose(&pool)));
+        password.shuffle(&mut rand::rng());
+
+        let mut secret = Secret::text();
+        secret.extend(password.iter());
+
+        Some(secret)
+    }
+
+    /// Entropy in bits of a secret generated with the current settings
+    ///
+    /// For passwords this counts every character as drawn from the whole pool,
+    /// which slightly overestimates it when minimum counts are set.
+    pub fn entropy(&self) -> f64 {
+        if self.passphrase {
+            return self.words as f64 * (WORD_COUNT as f64).log2();
+        }
+
+        let pool: usize = self.enabled().iter().map(|(c, _)| c.len()).sum();
+        if pool == 0 {
+            return 0.0;
+        }
+
+        self.length as f64 * (pool as f64).log2()
+    }
+
+    fn generate_passphrase(&self) -> Option<Secret<String>> {
+        if self.words == 0 {
+            return None;
+        }
+
+        let words: Vec<&str> = WORDS.lines().collect();
+
+        let mut passphrase = Secret::text();
+        for i in 0..self.words {
+            if i > 0 {
+                passphrase.push('-');
+            }
+            passphrase.push_str(choose(&words));
+        }
+
+        Some(passphrase)
+    }
+
+    /// Characters and minimum count of every enabled class
+    fn enabled(&self) -> Vec<(Vec<char>, usize)> {
+        Class::ALL
+            .into_iter()
+            .zip(self.classes)
+            .filter_map(|(class, minimum)| {
+                Some((class.characters(self.exclude_ambiguous), minimum?))
+            })
+            .collect()
+    }
+}
+
+/// Pick a uniformly random element
+fn choose<T: Copy>(list: &[T]) -> T {
+    list[rand::random_range(0..list.len())]
+}
+
+#[test]
+fn test() {
+    assert_eq!(WORDS.lines().count(), WORD_COUNT);
+
+    let mut generator = Generator {
+        length: 12,
+        classes: [Some(2), Some(3), Some(4), None],
+        ..Generator::default()
+    };
+
+    for _ in 0..100 {
+        let password = generator.generate().unwrap();
+        assert_eq!(password.chars().count(), 12);
+        assert!(password.chars().filter(char::is_ascii_lowercase).count() >= 2);
+        assert!(password.chars().filter(char::is_ascii_uppercase).count() >= 3);
+        assert!(password.chars().filter(char::is_ascii_digit).count() >= 4);
+        assert!(password.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert!(!password.chars().any(|c| AMBIGUOUS.contains(c)));
+    }
+
+    // 24 + 24 + 8 characters without the ambiguous ones
+    assert!((generator.entropy() - 12.0 * 56f64.log2()).abs() < 1e-9);
+
+    generator.length = 8;
+    assert!(generator.generate().is_none());
+    generator.classes = [None; 4];
+    assert!(generator.generate().is_none());
+    assert_eq!(generator.entropy(), 0.0);
+
+    generator.passphrase = true;
+    generator.words = 5;
+    let passphrase = generator.generate().unwrap();
+    let words: Vec<&str> = passphrase.split('-').collect();
+    assert_eq!(words.len(), 5);
+    assert!(words.iter().all(|word| WORDS.lines().any(|w| w == *word)));
+    assert_eq!(generator.entropy(), 55.0);
+}
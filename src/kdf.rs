@@ -0,0 +1,152 @@
+use crate::{secret::Secret, sha};
+
+/// Length of the random salt stored alongside the key derivation parameters
+pub const SALT_LEN: usize = 16;
+
+/// Key derivation function and its cost parameters, stored in the database
+/// as a string such as `pbkdf2-sha256$i=600000`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kdf {
+    Pbkdf2Sha256 { iterations: u32 },
+}
+
+impl Default for Kdf {
+    fn default() -> Self {
+        Self::Pbkdf2Sha256 {
+            iterations: 600_000,
+        }
+    }
+}
+
+impl Kdf {
+    pub fn parse(params: &str) -> Option<Self> {
+        let (name, params) = params.split_once('$')?;
+
+        match name {
+            "pbkdf2-sha256" => {
+                let iterations = params.strip_prefix("i=")?.parse().ok()?;
+                if iterations == 0 {
+                    return None;
+                }
+
+                Some(Self::Pbkdf2Sha256 { iterations })
+            }
+            _ => None,
+        }
+    }
+
+    /// Derive the password verifier and the encryption key from the master password
+    pub fn derive(&self, password: &[u8], salt: &[u8]) -> Keys {
+        let master = match *self {
+            Self::Pbkdf2Sha256 { iterations } => pbkdf2(password, salt, iterations),
+        };
+
+        // the verifier is stored in the database, so it must not reveal the encryption key
+        Keys {
+            verifier: sha::hmac(&*master, b"goonpass verifier"),
+            encryption: Secret::new(sha::hmac(&*master, b"goonpass encryption")),
+        }
+    }
+}
+
+impl std::fmt::Display for Kdf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pbkdf2Sha256 { iterations } => write!(f, "pbkdf2-sha256$i={iterations}"),
+        }
+    }
+}
+
+pub struct Keys {
+    pub verifier: [u8; 32],
+    pub encryption: Secret<[u8; 32]>,
+}
+
+/// Key for one purpose derived from the data key with HKDF (RFC 5869), so the data
+/// key itself only ever encrypts
+pub fn subkey(key: &[u8; 32], purpose: &[u8]) -> Secret<[u8; 32]> {
+    let prk = sha::hkdf_extract(&[], key);
+    let mut subkey = Secret::new([0; 32]);
+    sha::hkdf_expand(&*prk, purpose, &mut *subkey);
+
+    subkey
+}
+
+/// Compare two byte strings without exiting early on the first mismatch
+pub fn verify(lhs: &[u8], rhs: &[u8]) -> bool {
+    if lhs.len() != rhs.len() {
+        return false;
+    }
+
+    lhs.iter().zip(rhs).fold(0, |acc, (l, r)| acc | (l ^ r)) == 0
+}
+
+/// PBKDF2-HMAC-SHA256 (RFC 8018) with a single 32 byte output block
+fn pbkdf2(password: &[u8], salt: &[u8], iterations: u32) -> Secret<[u8; 32]> {
+    let mut message = salt.to_vec();
+    message.extend_from_slice(&1_u32.to_be_bytes());
+
+    let mut u = Secret::new(sha::hmac(password, &message));
+    let mut output = Secret::new(*u);
+
+    for _ in 1..iterations {
+        *u = sha::hmac(password, &*u);
+        for (o, u) in output.iter_mut().zip(u.iter()) {
+            *o ^= u;
+        }
+    }
+
+    output
+}
+
+#[test]
+fn test() {
+    // RFC 7914 section 11 and the widely used PBKDF2-HMAC-SHA256 vectors
+    assert_eq!(
+        *pbkdf2(b"password", b"salt", 1),
+        [
+            0x12, 0x0f, 0xb6, 0xcf, 0xfc, 0xf8, 0xb3, 0x2c, 0x43, 0xe7, 0x22, 0x52, 0x56, 0xc4,
+            0xf8, 0x37, 0xa8, 0x65, 0x48, 0xc9, 0x2c, 0xcc, 0x35, 0x48, 0x08, 0x05, 0x98, 0x7c,
+            0xb7, 0x0b, 0xe1, 0x7b
+        ]
+    );
+
+    assert_eq!(
+        *pbkdf2(b"password", b"salt", 2),
+        [
+            0xae, 0x4d, 0x0c, 0x95, 0xaf, 0x6b, 0x46, 0xd3, 0x2d, 0x0a, 0xdf, 0xf9, 0x28, 0xf0,
+            0x6d, 0xd0, 0x2a, 0x30, 0x3f, 0x8e, 0xf3, 0xc2, 0x51, 0xdf, 0xd6, 0xe2, 0xd8, 0x5a,
+            0x95, 0x47, 0x4c, 0x43
+        ]
+    );
+
+    assert_eq!(
+        *pbkdf2(b"password", b"salt", 4096),
+        [
+            0xc5, 0xe4, 0x78, 0xd5, 0x92, 0x88, 0xc8, 0x41, 0xaa, 0x53, 0x0d, 0xb6, 0x84, 0x5c,
+            0x4c, 0x8d, 0x96, 0x28, 0x93, 0xa0, 0x01, 0xce, 0x4e, 0x11, 0xa4, 0x96, 0x38, 0x73,
+            0xaa, 0x98, 0x13, 0x4a
+        ]
+    );
+
+    let kdf = Kdf::Pbkdf2Sha256 { iterations: 10 };
+    assert_eq!(Kdf::parse(&kdf.to_string()), Some(kdf));
+    assert_eq!(Kdf::parse("pbkdf2-sha256$i=0"), None);
+    assert_eq!(Kdf::parse("md5$i=1"), None);
+
+    let keys = kdf.derive(b"master", b"0123456789ABCDEF");
+    assert_ne!(keys.verifier, *keys.encryption);
+    assert!(verify(
+        &keys.verifier,
+        &kdf.derive(b"master", b"0123456789ABCDEF").verifier
+    ));
+    assert!(!verify(
+        &keys.verifier,
+        &kdf.derive(b"master!", b"0123456789ABCDEF").verifier
+    ));
+
+    let key = [7; 32];
+    assert_eq!(*subkey(&key, b"one"), *subkey(&key, b"one"));
+    assert_ne!(*subkey(&key, b"one"), *subkey(&key, b"two"));
+    assert_ne!(*subkey(&key, b"one"), key);
+}
@@ -0,0 +1,92 @@
+//! Key files, an optional second factor combined with the master password as KeePass
+//! does. Any file works, only the hash of its contents is used.
+
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{error::Error, secret::Secret, sha};
+
+/// Size of a generated key file
+const LEN: usize = 64;
+
+/// Size of the pieces a key file is read in
+const BUFFER_LEN: usize = 8192;
+
+/// Key file chosen on the login screen
+pub enum KeyFile {
+    /// An existing file
+    Open(PathBuf),
+    /// A file to generate, only when creating a vault
+    Generate(PathBuf),
+}
+
+impl KeyFile {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Open(path) | Self::Generate(path) => path,
+        }
+    }
+}
+
+/// Hash the contents of a key file, a piece at a time as it may be any large file
+pub fn read(path: &Path) -> Result<Secret<[u8; 32]>, Error> {
+    let mut file = std::fs::File::open(path)?;
+    let mut sha = sha::Sha256::new();
+    let mut buffer = Secret::new([0_u8; BUFFER_LEN]);
+
+    loop {
+        match file.read(&mut *buffer) {
+            Ok(0) => break,
+            Ok(len) => sha.update(&buffer[..len]),
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+
+    Ok(Secret::new(sha.finalize()))
+}
+
+/// Write a new key file of random bytes, never overwriting an existing file,
+/// and return its hash
+pub fn generate(path: &Path) -> Result<Secret<[u8; 32]>, Error> {
+    let mut bytes = Secret::new([0_u8; LEN]);
+    rand::fill(&mut *bytes);
+
+    let mut file = std::fs::File::create_new(path)?;
+    file.write_all(&*bytes)?;
+    file.sync_all()?;
+
+    Ok(Secret::new(sha::hash(&*bytes)))
+}
+
+/// Input to the key derivation: the master password alone, or the hash of the
+/// password's hash followed by the key file's hash
+pub fn combine(master: &str, key_file: Option<&[u8; 32]>) -> Secret<Vec<u8>> {
+    let Some(key_file) = key_file else {
+        return Secret::new(master.as_bytes().to_vec());
+    };
+
+    let mut composite = Secret::new(Vec::with_capacity(64));
+    composite.extend_from_slice(&sha::hash(master.as_bytes()));
+    composite.extend_from_slice(key_file);
+
+    Secret::new(sha::hash(&composite).to_vec())
+}
+
+#[test]
+fn test() {
+    let path = crate::TempPath::new();
+
+    let generated = generate(&path).unwrap();
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), LEN as u64);
+    assert_eq!(*read(&path).unwrap(), *generated);
+    assert!(generate(&path).is_err());
+
+    assert_eq!(combine("hunter2", None).as_slice(), b"hunter2");
+    let combined = combine("hunter2", Some(&generated));
+    assert_eq!(combined.len(), 32);
+    assert_ne!(*combined, *combine("hunter2", Some(&[0; 32])));
+    assert_ne!(*combined, *combine("hunter3", Some(&generated)));
+}
@@ -0,0 +1,124 @@
+//! Vaults written before the format version was tracked: a single salted
+//! SHA-256 hash in the `master` table, which was also the AES-256-ECB key
+
+use crate::{aes, associated_data, error::Error, secret::Secret, sha, sqlite};
+
+/// Whether the database still uses the legacy `master` table
+pub fn is_legacy(connection: &sqlite::Connection) -> Result<bool, Error> {
+    let table = connection.query_row(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'master'",
+        (),
+        |_| Ok(()),
+    )?;
+
+    Ok(table.is_some())
+}
+
+/// Check the master password against the legacy hash, returning the legacy key
+pub fn unlock(connection: &sqlite::Connection, master: &str) -> Result<Secret<[u8; 32]>, Error> {
+    let (hash, salt) = connection
+        .query_row("SELECT hash, salt FROM master", (), |row| {
+            row.get::<(Vec<u8>, Vec<u8>)>(0)
+        })?
+        .ok_or(Error::Corrupted("missing master password hash"))?;
+
+    let mut salted = Secret::new(Vec::with_capacity(master.len() + salt.len()));
+    salted.extend_from_slice(master.as_bytes());
+    salted.extend_from_slice(&salt);
+
+    if sha::hash(&salted) != hash.as_slice() {
+        return Err(Error::WrongPassword);
+    }
+
+    let key = hash
+        .try_into()
+        .map_err(|_| Error::Corrupted("invalid master password hash"))?;
+
+    Ok(Secret::new(key))
+}
+
+/// Re-encrypt every entry from the legacy key to `key` and drop the `master` table,
+/// inside a transaction so a crash leaves the legacy vault untouched
+pub fn migrate(
+    transaction: &sqlite::Transaction,
+    legacy: &[u8; 32],
+    key: &[u8; 32],
+) -> Result<(), Error> {
+    let entries = transaction.query_map(
+        "SELECT name, account, cyphertext FROM passwords",
+        (),
+        |row| row.get::<(String, String, Vec<u8>)>(0),
+    )?;
+
+    for (name, account, cyphertext) in entries {
+        let plaintext = aes::decrypt_ecb(&cyphertext, legacy).ok_or(Error::Decryption)?;
+
+        let aad = associated_data(&name, &account);
+        let cyphertext = aes::encrypt(&plaintext, key, &aad);
+
+        transaction.execute_params(
+            "UPDATE passwords SET cyphertext = ? WHERE name = ?",
+            (cyphertext, &name),
+        )?;
+    }
+
+    transaction.execute("DROP TABLE master")?;
+
+    Ok(())
+}
+
+#[test]
+fn test() {
+    let connection = sqlite::Connection::open(":memory:").unwrap();
+    connection
+        .execute(
+            "CREATE TABLE passwords (name STRING PRIMARY KEY, account STRING, cyphertext BLOB)",
+        )
+        .unwrap();
+    connection
+        .execute("CREATE TABLE master (hash BLOB, salt BLOB)")
+        .unwrap();
+
+    // a vault as written by the legacy `State::new` and `State::add_password`
+    let salt = [1, 2, 3, 4];
+    let legacy = sha::hash(b"hunter2\x01\x02\x03\x04");
+
+    let statement = connection
+        .prepare("INSERT INTO master (hash, salt) VALUES (?, ?)")
+        .unwrap();
+    statement.bind_blob(1, &legacy).unwrap();
+    statement.bind_blob(2, &salt).unwrap();
+    statement.execute().unwrap();
+
+    let cyphertext = aes::encrypt_ecb(b"correct horse", &legacy);
+    let statement = connection
+        .prepare("INSERT INTO passwords (name, account, cyphertext) VALUES (?, ?, ?)")
+        .unwrap();
+    statement.bind_text(1, "mail").unwrap();
+    statement.bind_text(2, "me@example.com").unwrap();
+    statement.bind_blob(3, &cyphertext).unwrap();
+    statement.execute().unwrap();
+
+    assert!(is_legacy(&connection).unwrap());
+    assert!(matches!(
+        unlock(&connection, "hunter3"),
+        Err(Error::WrongPassword)
+    ));
+    assert_eq!(*unlock(&connection, "hunter2").unwrap(), legacy);
+
+    let key = [7; 32];
+    let transaction = connection.transaction().unwrap();
+    migrate(&transaction, &legacy, &key).unwrap();
+    transaction.commit().unwrap();
+    assert!(!is_legacy(&connection).unwrap());
+
+    let statement = connection
+        .prepare("SELECT cyphertext FROM passwords WHERE name = 'mail'")
+        .unwrap();
+    let sqlite::Step::Row(row) = statement.step().unwrap() else {
+        panic!("entry lost during migration");
+    };
+    let aad = associated_data("mail", "me@example.com");
+    let plaintext = aes::decrypt(row.column_blob(0).unwrap(), &key, &aad).unwrap();
+    assert_eq!(plaintext.as_slice(), b"correct horse");
+}
@@ -1,10 +1,51 @@
 mod aes;
 mod algo;
+mod config;
+mod error;
+mod generator;
+mod kdf;
+mod keyfile;
+mod legacy;
+mod metadata;
+mod recovery;
+mod rekey;
+mod schema;
+mod secret;
 mod sha;
 mod sqlite;
+mod totp;
 mod ui;
 
 use eframe::egui;
+use error::Error;
+use secret::Secret;
+
+/// Version of the on-disk format, stored in the `meta` table
+/// - 2: entries encrypted with AES-256-GCM under the key derived from the master password
+/// - 3: entries encrypted under a random data key, stored wrapped in `master_key`
+/// - 4: the data key wrapped a second time under a recovery code
+/// - 5: the master password optionally combined with a key file
+const FORMAT: u32 = 5;
+
+/// Rows of `master_key`, each wrapping the same data key
+const PASSWORD_SLOT: &str = "password";
+const RECOVERY_SLOT: &str = "recovery";
+
+/// Associated data for the wrapped data key, so it can't be confused with an entry
+const DATA_KEY_AAD: &[u8] = b"goonpass data key";
+
+/// Vault offered when none has been opened yet, relative to the working directory
+const VAULT: &str = "db";
+
+/// How long a write waits for another connection to the vault, such as the one changing
+/// the master password, to finish its own
+const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Minutes of inactivity before the vault locks, unless changed in the settings
+const LOCK_AFTER: u32 = 5;
+
+/// Seconds before a copied password is cleared from the clipboard, unless changed in the settings
+const CLEAR_CLIPBOARD_AFTER: u32 = 30;
 
 fn main() {
     eframe::run_native(
@@ -15,168 +56,1292 @@ fn main() {
     .unwrap();
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 struct Password {
+    // key of the row, the name itself unless the metadata is encrypted
+    pub id: String,
     pub name: String,
     pub account: String,
     pub cyphertext: Vec<u8>,
+    pub url: String,
+    // encrypted like the password, `None` without notes
+    pub notes: Option<Vec<u8>>,
+    pub fields: Vec<Field>,
+    // authenticator secret, encrypted like the password
+    pub totp: Option<Vec<u8>>,
+    // unix time in seconds, `None` for entries saved before they were recorded
+    pub created: Option<i64>,
+    pub modified: Option<i64>,
+    pub last_used: Option<i64>,
+}
+
+/// A user-defined field of an entry, its value is always encrypted
+#[derive(Clone)]
+struct Field {
+    pub kind: FieldKind,
+    pub name: String,
+    pub cyphertext: Vec<u8>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FieldKind {
+    Text,
+    /// Masked like the password until revealed
+    Hidden,
+    Url,
+}
+
+impl FieldKind {
+    const ALL: [Self; 3] = [Self::Text, Self::Hidden, Self::Url];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Hidden => "hidden",
+            Self::Url => "url",
+        }
+    }
+
+    fn parse(kind: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|candidate| candidate.as_str() == kind)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Text => "Text",
+            Self::Hidden => "Hidden",
+            Self::Url => "URL",
+        }
+    }
+}
+
+/// A password on the clipboard, waiting to be cleared
+struct Copied {
+    // name of the entry, for the countdown on its row
+    name: String,
+    // keyed hash of the copied value under `State::clipboard_key`, so neither the
+    // plaintext nor a hash that could be brute-forced on its own stays in memory
+    mac: [u8; 32],
+    // time the clipboard is cleared, in egui time
+    expires: f64,
+}
+
+/// Fields of the "Change master password" form
+#[derive(Default)]
+struct ChangeMaster {
+    current_field: Secret<String>,
+    new_field: Secret<String>,
+    confirm_field: Secret<String>,
+}
+
+/// An entry being edited in place
+struct Edit {
+    // name of the entry before editing, used to find its row
+    original: String,
+    // contents of the text fields
+    name_field: String,
+    account_field: String,
+    plaintext_field: Secret<String>,
+    url_field: String,
+    notes_field: Secret<String>,
+    totp_field: Secret<String>,
+    fields: Vec<EditField>,
+}
+
+/// A custom field being edited, decrypted
+struct EditField {
+    kind: FieldKind,
+    name: String,
+    value: Secret<String>,
+}
+
+/// Decrypted notes and custom fields of the entry shown in detail
+struct Detail {
+    name: String,
+    notes: Secret<String>,
+    // values of the custom fields, and whether each hidden one is revealed
+    fields: Vec<(Secret<String>, bool)>,
 }
 
 struct State {
+    // vault file
+    vault: std::path::PathBuf,
     // list of passwords
     passwords: Vec<Password>,
-    // master password hash
-    master: [u8; 32],
+    // data key encrypting the entries, unwrapped with the master password
+    key: Secret<[u8; 32]>,
+    // hash of the key file the vault was unlocked with, kept when changing the master password
+    key_file: Option<Secret<[u8; 32]>>,
     // database connection
     connection: sqlite::Connection,
     // contents of the text fields
     name_field: String,
     account_field: String,
-    plaintext_field: String,
+    plaintext_field: Secret<String>,
+    url_field: String,
+    search_field: String,
+    // settings of the password generator
+    generator: generator::Generator,
+    // entry being edited, and entry shown in detail, if any
+    edit: Option<Edit>,
+    detail: Option<Detail>,
+    // authenticators of the entries shown so far by name, decrypted once rather than
+    // every frame. The secrets are wiped with the state.
+    totps: std::collections::HashMap<String, Result<Option<totp::Totp>, Error>>,
+    // minutes of inactivity before locking, 0 to never lock, and the last input
+    lock_after: u32,
+    last_activity: std::time::Instant,
+    // seconds before clearing the clipboard, 0 to never clear it, and the copied password
+    clear_clipboard_after: u32,
+    copied: Option<Copied>,
+    // random key for the hash of the copied password, new for every session
+    clipboard_key: Secret<[u8; 32]>,
+    // changing the master password, and its progress once started
+    change_master: ChangeMaster,
+    rekey: Option<rekey::Rekey>,
+    // recovery code to show until the user has written it down
+    recovery_code: Option<Secret<String>>,
+    // unlocked with the recovery code, a new master password must be set first
+    recovered: bool,
+    // whether entries are written with their metadata encrypted
+    encrypt_metadata: bool,
+    // error message shown at the bottom of the window, and when it disappears
+    toast: Option<(String, f64)>,
 }
 
 impl State {
-    pub fn new(master: &str) -> Self {
-        let connection = sqlite::Connection::open("db").unwrap();
-        connection
-            .execute("CREATE TABLE IF NOT EXISTS passwords (name STRING PRIMARY KEY, account STRING, cyphertext BLOB)")
-            .unwrap();
-        connection
-            .execute("CREATE TABLE IF NOT EXISTS master (hash BLOB, salt BLOB)")
-            .unwrap();
-
-        let master = if let sqlite::Step::Row(row) = connection
-            .prepare("SELECT hash, salt FROM master")
-            .unwrap()
-            .step()
-            .unwrap()
-        {
-            let hash = row.column_blob(0).unwrap();
-            let salt = row.column_blob(1).unwrap();
+    /// Unlock the vault, or create it. A new key file is only generated for a new vault.
+    pub fn new(
+        vault: &std::path::Path,
+        master: &str,
+        key_file: Option<&keyfile::KeyFile>,
+    ) -> Result<Self, Error> {
+        let connection = connect(vault)?;
+        let is_new = !legacy::is_legacy(&connection)? && !has_master_key(&connection)?;
+
+        let key_file = match key_file {
+            None => None,
+            Some(keyfile::KeyFile::Open(path)) => Some(keyfile::read(path)?),
+            Some(keyfile::KeyFile::Generate(path)) if is_new => Some(keyfile::generate(path)?),
+            Some(keyfile::KeyFile::Generate(_)) => return Err(Error::VaultExists),
+        };
+        let key_file_hash = key_file.as_deref();
 
-            let mut master = master.as_bytes().to_vec();
-            master.extend_from_slice(salt);
+        let mut recovery_code = None;
+        let key = if legacy::is_legacy(&connection)? {
+            let legacy = legacy::unlock(&connection, master)?;
 
-            if sha::hash(&master) != row.column_blob(0).unwrap() {
-                todo!("wrong master password");
-            }
+            // upgrade in a single transaction, so a crash leaves the legacy vault intact
+            let transaction = connection.transaction()?;
+            let key = create_master_key(&transaction, master, key_file_hash)?;
+            recovery_code = Some(create_recovery_code(&transaction, &key)?);
+            legacy::migrate(&transaction, &legacy, &key)?;
+            transaction.commit()?;
 
-            hash.try_into().unwrap()
+            key
+        } else if let Some(key) = unlock(&connection, PASSWORD_SLOT, master, key_file_hash)? {
+            key
         } else {
-            let salt = rand::random::<[u8; 4]>();
+            let transaction = connection.transaction()?;
+            let key = create_master_key(&transaction, master, key_file_hash)?;
+            recovery_code = Some(create_recovery_code(&transaction, &key)?);
+            // new vaults hide their metadata, existing ones keep their choice
+            set_setting(&transaction, "encrypt_metadata", 1)?;
+            transaction.commit()?;
+
+            key
+        };
 
-            let mut master = master.as_bytes().to_vec();
-            master.extend_from_slice(&salt);
+        let mut state = Self::open(vault, connection, key)?;
+        state.key_file = key_file;
+        state.recovery_code = recovery_code;
 
-            let hash = sha::hash(&master);
+        Ok(state)
+    }
 
-            let statement = connection
-                .prepare("INSERT INTO master (hash, salt) VALUES (?, ?)")
-                .unwrap();
-            statement.bind_blob(1, &hash).unwrap();
-            statement.bind_blob(2, &salt).unwrap();
-            statement.execute().unwrap();
+    /// Unlock with the recovery code, the user then has to choose a new master password
+    pub fn recover(vault: &std::path::Path, code: &str) -> Result<Self, Error> {
+        // connecting would create the vault
+        if !vault.exists() {
+            return Err(Error::VaultNotFound);
+        }
+        let connection = connect(vault)?;
 
-            hash
+        let key = match unlock(&connection, RECOVERY_SLOT, &recovery::normalize(code), None) {
+            Ok(Some(key)) => key,
+            Ok(None) | Err(Error::WrongPassword) => return Err(Error::WrongRecoveryCode),
+            Err(error) => return Err(error),
         };
 
-        let mut passwords = Vec::new();
-        let statement = connection
-            .prepare("SELECT name, account, cyphertext FROM passwords")
-            .unwrap();
-        for row in &statement.rows() {
-            let name = row.column_text(0).unwrap().to_string();
-            let account = row.column_text(1).unwrap().to_string();
-            let cyphertext = row.column_blob(2).unwrap().to_vec();
+        let mut state = Self::open(vault, connection, key)?;
+        state.recovered = true;
 
-            passwords.push(Password {
-                name,
-                account,
-                cyphertext,
-            });
-        }
+        Ok(state)
+    }
 
-        algo::sort(&mut passwords);
+    fn open(
+        vault: &std::path::Path,
+        connection: sqlite::Connection,
+        key: Secret<[u8; 32]>,
+    ) -> Result<Self, Error> {
+        let passwords = load_passwords(&connection, &key)?;
+        fill_lookups(&connection, &passwords, &key)?;
 
-        Self {
+        let encrypt_metadata = setting(&connection, "encrypt_metadata", 0)? != 0;
+        let lock_after = setting(&connection, "lock_after", LOCK_AFTER)?;
+        let clear_clipboard_after =
+            setting(&connection, "clear_clipboard_after", CLEAR_CLIPBOARD_AFTER)?;
+        let mut clipboard_key = Secret::new([0; 32]);
+        rand::fill(&mut *clipboard_key);
+
+        Ok(Self {
+            vault: vault.to_path_buf(),
             passwords,
-            master,
+            key,
+            key_file: None,
             connection,
             name_field: String::new(),
             account_field: String::new(),
-            plaintext_field: String::new(),
+            plaintext_field: Secret::text(),
+            url_field: String::new(),
+            search_field: String::new(),
+            generator: generator::Generator::default(),
+            edit: None,
+            detail: None,
+            totps: std::collections::HashMap::new(),
+            lock_after,
+            last_activity: std::time::Instant::now(),
+            clear_clipboard_after,
+            copied: None,
+            clipboard_key,
+            change_master: ChangeMaster::default(),
+            rekey: None,
+            recovery_code: None,
+            recovered: false,
+            encrypt_metadata,
+            toast: None,
+        })
+    }
+
+    fn set_lock_after(&mut self, minutes: u32) -> Result<(), Error> {
+        set_setting(&self.connection, "lock_after", minutes)?;
+        self.lock_after = minutes;
+
+        Ok(())
+    }
+
+    fn change_master(&mut self, ctx: &egui::Context) {
+        let form = std::mem::take(&mut self.change_master);
+        let key_file = self.key_file.as_ref().map(|hash| Secret::new(**hash));
+        self.rekey = Some(rekey::Rekey::start(
+            self.vault.clone(),
+            form.current_field,
+            form.new_field,
+            key_file,
+            ctx,
+        ));
+    }
+
+    /// Set the master password after recovering, the old one is forgotten along with
+    /// the key file, which may be what was lost
+    fn set_master(&mut self) -> Result<(), Error> {
+        let form = std::mem::take(&mut self.change_master);
+
+        let wrapped = WrappedKey::new(kdf::Kdf::default(), &form.new_field, None, &self.key);
+
+        let transaction = self.connection.transaction()?;
+        wrapped.store(&transaction, PASSWORD_SLOT)?;
+        transaction.commit()?;
+
+        self.recovered = false;
+
+        Ok(())
+    }
+
+    /// Replace the recovery code, the old one stops working
+    fn new_recovery_code(&mut self) -> Result<(), Error> {
+        let transaction = self.connection.transaction()?;
+        let code = create_recovery_code(&transaction, &self.key)?;
+        transaction.commit()?;
+
+        self.recovery_code = Some(code);
+
+        Ok(())
+    }
+
+    /// Rewrite every entry with its metadata encrypted, or back in the clear
+    fn set_encrypt_metadata(&mut self, encrypt: bool) -> Result<(), Error> {
+        let mut passwords = self.passwords.clone();
+
+        let transaction = self.connection.transaction()?;
+        for password in &mut passwords {
+            delete_password(&transaction, &password.id)?;
+            password.id = if encrypt {
+                random_id()
+            } else {
+                password.name.clone()
+            };
+            write_password(&transaction, password, encrypt.then_some(&*self.key))?;
         }
+        set_setting(&transaction, "encrypt_metadata", u32::from(encrypt))?;
+        transaction.commit()?;
+
+        self.passwords = passwords;
+        self.encrypt_metadata = encrypt;
+
+        Ok(())
     }
 
-    fn add_password(&mut self) {
+    fn set_clear_clipboard_after(&mut self, seconds: u32) -> Result<(), Error> {
+        set_setting(&self.connection, "clear_clipboard_after", seconds)?;
+        self.clear_clipboard_after = seconds;
+
+        Ok(())
+    }
+
+    fn add_password(&mut self) -> Result<(), Error> {
         let name = self.name_field.clone();
         let account = self.account_field.clone();
-        let plaintext = self.plaintext_field.clone();
-
-        // validation
-        // - `name` must be between 1 and 30 characters
-        // - `account` must be between 1 and 40 characters
-        // - `plaintext` must be between 1 and 40 characters
-        if name.is_empty()
-            || account.is_empty()
-            || plaintext.is_empty()
-            || name.len() > 30
-            || account.len() > 40
-            || plaintext.len() > 40
-        {
-            return;
-        }
+        let plaintext = &self.plaintext_field;
 
-        // return if a password with the same name already exists
-        if algo::contains(&name, &self.passwords) {
-            return;
+        if !valid(&name, &account, plaintext) {
+            return Ok(());
         }
 
-        let cyphertext = aes::encrypt(plaintext.as_bytes(), &self.master);
+        let aad = associated_data(&name, &account);
+        let cyphertext = aes::encrypt(plaintext.as_bytes(), &self.key, &aad);
+        let now = now();
 
-        let statement = self
-            .connection
-            .prepare("INSERT INTO passwords (name, account, cyphertext) VALUES (?, ?, ?)")
-            .unwrap();
-        statement.bind_text(1, &name).unwrap();
-        statement.bind_text(2, &account).unwrap();
-        statement.bind_blob(3, &cyphertext).unwrap();
-        statement.execute().unwrap();
-
-        self.passwords.push(Password {
+        let password = Password {
+            id: if self.encrypt_metadata {
+                random_id()
+            } else {
+                name.clone()
+            },
             name,
             account,
             cyphertext,
-        });
+            url: self.url_field.trim().to_string(),
+            notes: None,
+            fields: Vec::new(),
+            totp: None,
+            created: Some(now),
+            modified: Some(now),
+            last_used: None,
+        };
+
+        let transaction = self.connection.transaction()?;
+        write_password(
+            &transaction,
+            &password,
+            self.encrypt_metadata.then_some(&*self.key),
+        )?;
+        transaction.commit()?;
+
+        self.passwords.push(password);
 
         algo::sort(&mut self.passwords);
 
         self.name_field.clear();
         self.account_field.clear();
         self.plaintext_field.clear();
+        self.url_field.clear();
+
+        Ok(())
     }
 
-    fn remove_password(&mut self, index: usize, name: &str) {
+    fn remove_password(&mut self, index: usize, password: &Password) -> Result<(), Error> {
+        let transaction = self.connection.transaction()?;
+        delete_password(&transaction, &password.id)?;
+        transaction.commit()?;
+
         self.passwords.remove(index);
+        self.totps.remove(&password.name);
 
-        let statement = self
-            .connection
-            .prepare("DELETE FROM passwords WHERE name = ?")
-            .unwrap();
-        statement.bind_text(1, &name).unwrap();
-        statement.execute().unwrap();
+        if self
+            .detail
+            .as_ref()
+            .is_some_and(|detail| detail.name == password.name)
+        {
+            self.detail = None;
+        }
+
+        Ok(())
+    }
+
+    fn start_edit(&mut self, password: &Password) -> Result<(), Error> {
+        let aad = associated_data(&password.name, &password.account);
+        let plaintext_field = decrypt_text(&password.cyphertext, &self.key, &aad)?;
+        let notes_field = self.decrypt_notes(password)?;
+        let totp_field = match &password.totp {
+            Some(totp) => {
+                let aad = totp_data(&password.name, &password.account);
+                decrypt_text(totp, &self.key, &aad)?
+            }
+            None => Secret::text(),
+        };
+
+        let fields = password
+            .fields
+            .iter()
+            .zip(self.decrypt_fields(password)?)
+            .map(|(field, value)| EditField {
+                kind: field.kind,
+                name: field.name.clone(),
+                value,
+            })
+            .collect();
+
+        self.edit = Some(Edit {
+            original: password.name.clone(),
+            name_field: password.name.clone(),
+            account_field: password.account.clone(),
+            plaintext_field,
+            url_field: password.url.clone(),
+            notes_field,
+            totp_field,
+            fields,
+        });
+
+        Ok(())
+    }
+
+    fn save_edit(&mut self) -> Result<(), Error> {
+        let Some(edit) = &self.edit else {
+            return Ok(());
+        };
+
+        let name = edit.name_field.clone();
+        let account = edit.account_field.clone();
+        let plaintext = &edit.plaintext_field;
+
+        if !valid(&name, &account, plaintext)
+            || !fits(&edit.notes_field)
+            || !fits(&edit.totp_field)
+            || edit
+                .fields
+                .iter()
+                .any(|field| field.name.is_empty() || !fits(&field.value))
+        {
+            return Ok(());
+        }
+
+        // the name and account are bound into the associated data, so always re-encrypt
+        let aad = associated_data(&name, &account);
+        let cyphertext = aes::encrypt(plaintext.as_bytes(), &self.key, &aad);
+        let url = edit.url_field.trim().to_string();
+        let notes = (!edit.notes_field.is_empty()).then(|| {
+            let aad = notes_data(&name, &account);
+            aes::encrypt(edit.notes_field.as_bytes(), &self.key, &aad)
+        });
+        if !edit.totp_field.trim().is_empty() && totp::Totp::parse(&edit.totp_field).is_none() {
+            return Err(Error::InvalidTotp);
+        }
+        let totp = (!edit.totp_field.trim().is_empty()).then(|| {
+            let aad = totp_data(&name, &account);
+            aes::encrypt(edit.totp_field.trim().as_bytes(), &self.key, &aad)
+        });
+        let fields: Vec<Field> = edit
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(position, field)| {
+                let aad = field_data(&name, &account, position, field.kind, &field.name);
+                Field {
+                    kind: field.kind,
+                    name: field.name.clone(),
+                    cyphertext: aes::encrypt(field.value.as_bytes(), &self.key, &aad),
+                }
+            })
+            .collect();
+
+        let Some(password) = self
+            .passwords
+            .iter_mut()
+            .find(|password| password.name == edit.original)
+        else {
+            return Ok(());
+        };
+
+        // a plain row is keyed by the new name, an encrypted one keeps its opaque id
+        let edited = Password {
+            id: if !self.encrypt_metadata {
+                name.clone()
+            } else if password.id == password.name {
+                random_id()
+            } else {
+                password.id.clone()
+            },
+            name,
+            account,
+            cyphertext,
+            url,
+            notes,
+            fields,
+            totp,
+            created: password.created,
+            modified: Some(now()),
+            last_used: password.last_used,
+        };
+
+        let transaction = self.connection.transaction()?;
+        delete_password(&transaction, &password.id)?;
+        write_password(
+            &transaction,
+            &edited,
+            self.encrypt_metadata.then_some(&*self.key),
+        )?;
+        transaction.commit()?;
+
+        *password = edited;
+
+        algo::sort(&mut self.passwords);
+        self.edit = None;
+        self.detail = None;
+        // the entry may have been renamed, or its authenticator changed
+        self.totps.clear();
+
+        Ok(())
+    }
+
+    /// Show the notes and custom fields of an entry, or hide them if already shown
+    fn toggle_detail(&mut self, password: &Password) -> Result<(), Error> {
+        if self
+            .detail
+            .as_ref()
+            .is_some_and(|detail| detail.name == password.name)
+        {
+            self.detail = None;
+            return Ok(());
+        }
+
+        self.detail = Some(Detail {
+            name: password.name.clone(),
+            notes: self.decrypt_notes(password)?,
+            fields: self
+                .decrypt_fields(password)?
+                .into_iter()
+                .map(|value| (value, false))
+                .collect(),
+        });
+
+        Ok(())
+    }
+
+    fn decrypt_notes(&self, password: &Password) -> Result<Secret<String>, Error> {
+        let Some(notes) = &password.notes else {
+            return Ok(Secret::text());
+        };
+
+        let aad = notes_data(&password.name, &password.account);
+        decrypt_text(notes, &self.key, &aad)
+    }
+
+    /// The authenticator of an entry, if it has one
+    fn decrypt_totp(&self, password: &Password) -> Result<Option<totp::Totp>, Error> {
+        let Some(totp) = &password.totp else {
+            return Ok(None);
+        };
+
+        let aad = totp_data(&password.name, &password.account);
+        let text = decrypt_text(totp, &self.key, &aad)?;
+
+        totp::Totp::parse(&text).map(Some).ok_or(Error::InvalidTotp)
+    }
+
+    fn decrypt_fields(&self, password: &Password) -> Result<Vec<Secret<String>>, Error> {
+        password
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(position, field)| {
+                let aad = field_data(
+                    &password.name,
+                    &password.account,
+                    position,
+                    field.kind,
+                    &field.name,
+                );
+                decrypt_text(&field.cyphertext, &self.key, &aad)
+            })
+            .collect()
+    }
+
+    fn copy_password(&mut self, password: &Password, ctx: &egui::Context) -> Result<(), Error> {
+        let aad = associated_data(&password.name, &password.account);
+        let plaintext = decrypt_text(&password.cyphertext, &self.key, &aad)?;
+
+        self.copy(&password.name, &plaintext, ctx)
+    }
+
+    /// Copy a value of the entry `name`, which counts as using the entry
+    fn copy(&mut self, name: &str, plaintext: &str, ctx: &egui::Context) -> Result<(), Error> {
+        ctx.copy_text(plaintext.to_string());
+
+        self.copied = (self.clear_clipboard_after > 0).then(|| Copied {
+            name: name.to_string(),
+            mac: sha::hmac(&*self.clipboard_key, plaintext.as_bytes()),
+            expires: ctx.input(|input| input.time) + f64::from(self.clear_clipboard_after),
+        });
+
+        if let Some(password) = self
+            .passwords
+            .iter_mut()
+            .find(|password| password.name == name)
+        {
+            password.last_used = Some(now());
+            touch_password(
+                &self.connection,
+                password,
+                self.encrypt_metadata.then_some(&*self.key),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for State {
+    // the key and plaintext fields are wiped by `Secret`, but the clipboard isn't
+    fn drop(&mut self) {
+        if let Some(copied) = &self.copied {
+            clear_clipboard(&self.clipboard_key, &copied.mac).ok();
+        }
+    }
+}
+
+/// Clear the clipboard, unless it was overwritten with something else since copying
+fn clear_clipboard(key: &[u8; 32], mac: &[u8; 32]) -> Result<(), Error> {
+    let mut clipboard = arboard::Clipboard::new()?;
+
+    // getting fails if the clipboard is empty or holds something other than text
+    if let Ok(text) = clipboard.get_text().map(Secret::new)
+        && kdf::verify(&sha::hmac(key, text.as_bytes()), mac)
+    {
+        clipboard.clear()?;
+    }
+
+    Ok(())
+}
+
+/// Read a numeric setting from the `meta` table
+fn setting(connection: &sqlite::Connection, key: &str, default: u32) -> Result<u32, Error> {
+    let value = connection.query_row("SELECT value FROM meta WHERE key = ?", (key,), |row| {
+        row.get::<String>(0)
+    })?;
+
+    Ok(value
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default))
+}
+
+fn set_setting(connection: &sqlite::Connection, key: &str, value: u32) -> Result<(), Error> {
+    connection.execute_params(
+        "INSERT OR REPLACE INTO meta (key, value) VALUES (?, ?)",
+        (key, value.to_string()),
+    )?;
+
+    Ok(())
+}
+
+/// Validation shared by new and edited entries
+/// - `name` must be between 1 and 30 characters
+/// - `account` must be between 1 and 40 characters
+/// - `plaintext` must be between 1 and `TEXT_LIMIT` characters
+fn valid(name: &str, account: &str, plaintext: &str) -> bool {
+    !name.is_empty()
+        && !account.is_empty()
+        && !plaintext.is_empty()
+        && name.len() <= 30
+        && account.len() <= 40
+        && fits(plaintext)
+}
+
+/// Whether a secret fits in the buffer of [`Secret::text`], which would leave an
+/// unwiped copy behind if it had to grow
+fn fits(text: &str) -> bool {
+    text.chars().count() <= secret::TEXT_LIMIT
+}
+
+/// Current unix time in seconds
+fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+/// Open the vault, creating it if it doesn't exist, and bring its schema up to date
+fn connect(vault: &std::path::Path) -> Result<sqlite::Connection, Error> {
+    let path = vault
+        .to_str()
+        .ok_or_else(|| Error::InvalidPath(vault.to_path_buf()))?;
+    let connection = sqlite::Connection::open(path)?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+    // overwrite deleted rows, so names rewritten by `set_encrypt_metadata` or removed
+    // entries don't linger in free pages
+    connection.execute("PRAGMA secure_delete = ON")?;
+    connection.migrate(schema::MIGRATIONS)?;
+
+    let format =
+        connection.query_row("SELECT value FROM meta WHERE key = 'format'", (), |row| {
+            row.get::<String>(0)
+        })?;
+    if let Some(format) = format {
+        let format: u32 = format
+            .parse()
+            .map_err(|_| Error::Corrupted("invalid format version"))?;
+        if format > FORMAT {
+            return Err(Error::UnsupportedFormat(format));
+        }
+    }
+
+    Ok(connection)
+}
+
+/// Whether the vault has been created, and so has a master password slot
+fn has_master_key(connection: &sqlite::Connection) -> Result<bool, Error> {
+    let slot = connection.query_row(
+        "SELECT 1 FROM master_key WHERE slot = ?",
+        (PASSWORD_SLOT,),
+        |_| Ok(()),
+    )?;
+
+    Ok(slot.is_some())
+}
+
+/// Whether logging in to `vault` creates it: the file doesn't exist, or it has no
+/// master password yet, like one left behind by a failed first login
+fn is_new(vault: &std::path::Path) -> bool {
+    if !vault.exists() {
+        return true;
+    }
+
+    // anything that can't be read as a vault is reported when logging in
+    let Some(connection) = vault
+        .to_str()
+        .and_then(|path| sqlite::Connection::open(path).ok())
+    else {
+        return false;
+    };
+
+    matches!(legacy::is_legacy(&connection), Ok(false))
+        && matches!(has_master_key(&connection), Ok(false))
+}
+
+/// Check `secret`, and the hash of the key file if the slot needs one, against the
+/// verifier of a key slot and unwrap the data key, or return `None` if the slot doesn't exist
+fn unlock(
+    connection: &sqlite::Connection,
+    slot: &str,
+    secret: &str,
+    key_file: Option<&[u8; 32]>,
+) -> Result<Option<Secret<[u8; 32]>>, Error> {
+    let Some((params, salt, verifier, wrapped, uses_key_file)) = connection.query_row(
+        "SELECT kdf, salt, verifier, wrapped_key, key_file FROM master_key WHERE slot = ?",
+        (slot,),
+        |row| row.get::<(String, Vec<u8>, Vec<u8>, Option<Vec<u8>>, bool)>(0),
+    )?
+    else {
+        return Ok(None);
+    };
+
+    match (uses_key_file, key_file.is_some()) {
+        (true, false) => return Err(Error::KeyFileRequired),
+        (false, true) => return Err(Error::KeyFileNotUsed),
+        _ => {}
+    }
+
+    let kdf =
+        kdf::Kdf::parse(&params).ok_or(Error::Corrupted("invalid key derivation parameters"))?;
+
+    let keys = kdf.derive(&keyfile::combine(secret, key_file), &salt);
+    if !kdf::verify(&keys.verifier, &verifier) {
+        return Err(if uses_key_file {
+            Error::WrongPasswordOrKeyFile
+        } else {
+            Error::WrongPassword
+        });
+    }
+
+    if let Some(wrapped) = wrapped {
+        let bytes = aes::decrypt(&wrapped, &keys.encryption, DATA_KEY_AAD)?;
+
+        let mut key = Secret::new([0; 32]);
+        if bytes.len() != key.len() {
+            return Err(Error::Corrupted("invalid data key"));
+        }
+        key.copy_from_slice(&bytes);
+
+        return Ok(Some(key));
+    }
+
+    // format 2 encrypted the entries with the derived key itself, move them to a data key
+    let transaction = connection.transaction()?;
+    let key = random_key();
+    reencrypt(&transaction, &keys.encryption, &key)?;
+    transaction.execute_params(
+        "UPDATE master_key SET wrapped_key = ? WHERE slot = ?",
+        (aes::encrypt(&*key, &keys.encryption, DATA_KEY_AAD), slot),
+    )?;
+    set_format(&transaction)?;
+    transaction.commit()?;
+
+    Ok(Some(key))
+}
+
+/// Read every entry with its custom fields, decrypting the metadata of the rows that
+/// have it encrypted, sorted by name
+fn load_passwords(connection: &sqlite::Connection, key: &[u8; 32]) -> Result<Vec<Password>, Error> {
+    let rows = connection.query_map(
+        "SELECT name, account, cyphertext, url, notes, created, modified, last_used, metadata, totp
+         FROM passwords",
+        (),
+        |row| {
+            let password = Password {
+                id: row.get(0)?,
+                name: row.get(0)?,
+                account: row.get(1)?,
+                cyphertext: row.get(2)?,
+                url: row.get(3)?,
+                notes: row.get(4)?,
+                fields: Vec::new(),
+                totp: row.get(9)?,
+                created: row.get(5)?,
+                modified: row.get(6)?,
+                last_used: row.get(7)?,
+            };
+
+            Ok((password, row.get::<Option<Vec<u8>>>(8)?))
+        },
+    )?;
+
+    let mut passwords = Vec::with_capacity(rows.len());
+    for (password, metadata) in rows {
+        let Some(metadata) = metadata else {
+            passwords.push(password);
+            continue;
+        };
+
+        passwords.push(Password {
+            cyphertext: password.cyphertext,
+            notes: password.notes,
+            totp: password.totp,
+            ..metadata::decrypt(&password.id, &metadata, key)?
+        });
+    }
+
+    let fields = connection.query_map(
+        "SELECT entry, position, kind, name, cyphertext FROM fields ORDER BY entry, position",
+        (),
+        |row| row.get::<(String, usize, String, String, Vec<u8>)>(0),
+    )?;
+    for (entry, position, kind, name, cyphertext) in fields {
+        let password = passwords
+            .iter_mut()
+            .find(|password| password.id == entry)
+            .ok_or(Error::Corrupted("field of a missing entry"))?;
+
+        // the kind and name of an encrypted entry's fields come from its metadata
+        if let Some(field) = password.fields.get_mut(position)
+            && field.cyphertext.is_empty()
+        {
+            field.cyphertext = cyphertext;
+            continue;
+        }
+
+        let kind = FieldKind::parse(&kind).ok_or(Error::Corrupted("invalid field kind"))?;
+        password.fields.push(Field {
+            kind,
+            name,
+            cyphertext,
+        });
+    }
+
+    algo::sort(&mut passwords);
+
+    Ok(passwords)
+}
+
+/// Create the data key of a new vault and store it wrapped under the master password,
+/// and the key file if there is one
+fn create_master_key(
+    transaction: &sqlite::Transaction,
+    master: &str,
+    key_file: Option<&[u8; 32]>,
+) -> Result<Secret<[u8; 32]>, Error> {
+    let key = random_key();
+    wrap_key(
+        transaction,
+        PASSWORD_SLOT,
+        kdf::Kdf::default(),
+        master,
+        key_file,
+        &key,
+    )?;
+
+    Ok(key)
+}
+
+/// Wrap the data key under a new recovery code, returning the code to show to the user
+fn create_recovery_code(
+    transaction: &sqlite::Transaction,
+    key: &[u8; 32],
+) -> Result<Secret<String>, Error> {
+    let code = recovery::generate();
+    let normalized = recovery::normalize(&code);
+    wrap_key(
+        transaction,
+        RECOVERY_SLOT,
+        recovery::KDF,
+        &normalized,
+        None,
+        key,
+    )?;
+
+    Ok(code)
+}
+
+/// Store the data key in a key slot, wrapped under a key derived from `secret` and
+/// the key file hash, replacing the previous contents of the slot
+fn wrap_key(
+    transaction: &sqlite::Transaction,
+    slot: &str,
+    kdf: kdf::Kdf,
+    secret: &str,
+    key_file: Option<&[u8; 32]>,
+    key: &[u8; 32],
+) -> Result<(), Error> {
+    WrappedKey::new(kdf, secret, key_file, key).store(transaction, slot)
+}
+
+/// The data key wrapped for a key slot, derived before taking the write lock, since
+/// the key derivation takes seconds and any other write would have to wait for it
+struct WrappedKey {
+    kdf: kdf::Kdf,
+    salt: [u8; kdf::SALT_LEN],
+    verifier: [u8; 32],
+    wrapped: Vec<u8>,
+    key_file: bool,
+}
+
+impl WrappedKey {
+    fn new(kdf: kdf::Kdf, secret: &str, key_file: Option<&[u8; 32]>, key: &[u8; 32]) -> Self {
+        let salt = rand::random::<[u8; kdf::SALT_LEN]>();
+        let keys = kdf.derive(&keyfile::combine(secret, key_file), &salt);
+
+        Self {
+            kdf,
+            salt,
+            verifier: keys.verifier,
+            wrapped: aes::encrypt(key, &keys.encryption, DATA_KEY_AAD),
+            key_file: key_file.is_some(),
+        }
     }
 
-    fn copy_password(&self, password: &Password, ctx: &egui::Context) {
-        let bytes = aes::decrypt(&password.cyphertext, &self.master);
-        let plaintext = String::from_utf8_lossy(&bytes);
-        ctx.copy_text(plaintext.into_owned());
+    /// Replace the previous contents of `slot`
+    fn store(&self, transaction: &sqlite::Transaction, slot: &str) -> Result<(), Error> {
+        transaction.execute_params("DELETE FROM master_key WHERE slot = ?", (slot,))?;
+        transaction.execute_params(
+            "INSERT INTO master_key (slot, kdf, salt, verifier, wrapped_key, key_file)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            (
+                slot,
+                self.kdf.to_string(),
+                self.salt,
+                self.verifier,
+                &self.wrapped,
+                self.key_file,
+            ),
+        )?;
+        set_format(transaction)?;
+
+        Ok(())
+    }
+}
+
+fn random_key() -> Secret<[u8; 32]> {
+    let mut key = Secret::new([0; 32]);
+    rand::fill(&mut *key);
+
+    key
+}
+
+fn set_format(transaction: &sqlite::Transaction) -> Result<(), Error> {
+    transaction.execute_params(
+        "INSERT OR REPLACE INTO meta (key, value) VALUES ('format', ?)",
+        (FORMAT.to_string(),),
+    )?;
+
+    Ok(())
+}
+
+/// Re-encrypt every entry from the `old` key to the `new` one
+fn reencrypt(
+    transaction: &sqlite::Transaction,
+    old: &[u8; 32],
+    new: &[u8; 32],
+) -> Result<(), Error> {
+    let entries = transaction.query_map(
+        "SELECT name, account, cyphertext FROM passwords",
+        (),
+        |row| row.get::<(String, String, Vec<u8>)>(0),
+    )?;
+
+    for (name, account, cyphertext) in entries {
+        let aad = associated_data(&name, &account);
+        let plaintext = aes::decrypt(&cyphertext, old, &aad)?;
+        let cyphertext = aes::encrypt(&plaintext, new, &aad);
+
+        transaction.execute_params(
+            "UPDATE passwords SET cyphertext = ? WHERE name = ?",
+            (cyphertext, &name),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Write an entry and its custom fields, replacing the row with the same id. With a
+/// `metadata_key`, the name, account, website, times and field names are only
+/// stored encrypted, and the row is keyed by the entry's opaque id.
+fn write_password(
+    transaction: &sqlite::Transaction,
+    password: &Password,
+    metadata_key: Option<&[u8; 32]>,
+) -> Result<(), Error> {
+    transaction.execute_params("DELETE FROM fields WHERE entry = ?", (&password.id,))?;
+
+    if let Some(key) = metadata_key {
+        transaction.execute_params(
+            "INSERT INTO passwords (name, account, cyphertext, url, notes, totp, metadata, lookup)
+             VALUES (?, '', ?, '', ?, ?, ?, ?)",
+            (
+                &password.id,
+                &password.cyphertext,
+                &password.notes,
+                &password.totp,
+                metadata::encrypt(password, key),
+                metadata::lookup(&password.name, key),
+            ),
+        )
+        .map_err(|error| duplicate(error, &password.name))?;
+    } else {
+        transaction
+            .execute_params(
+                "INSERT INTO passwords
+             (name, account, cyphertext, url, notes, totp, created, modified, last_used)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                (
+                    &password.id,
+                    &password.account,
+                    &password.cyphertext,
+                    &password.url,
+                    &password.notes,
+                    &password.totp,
+                    password.created,
+                    password.modified,
+                    password.last_used,
+                ),
+            )
+            .map_err(|error| duplicate(error, &password.name))?;
+    }
+
+    for (position, field) in password.fields.iter().enumerate() {
+        let (kind, name) = match metadata_key {
+            Some(_) => ("", ""),
+            None => (field.kind.as_str(), field.name.as_str()),
+        };
+
+        transaction.execute_params(
+            "INSERT INTO fields (entry, position, kind, name, cyphertext) VALUES (?, ?, ?, ?, ?)",
+            (&password.id, position, kind, name, &field.cyphertext),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Record the last use of an entry, only rewriting the column that holds its time
+fn touch_password(
+    connection: &sqlite::Connection,
+    password: &Password,
+    metadata_key: Option<&[u8; 32]>,
+) -> Result<(), Error> {
+    match metadata_key {
+        Some(key) => connection.execute_params(
+            "UPDATE passwords SET metadata = ? WHERE name = ?",
+            (metadata::encrypt(password, key), &password.id),
+        )?,
+        None => connection.execute_params(
+            "UPDATE passwords SET last_used = ? WHERE name = ?",
+            (password.last_used, &password.id),
+        )?,
+    }
+
+    if connection.changes() != 1 {
+        return Err(MISSING_ENTRY);
+    }
+
+    Ok(())
+}
+
+/// Delete the row of an entry and its custom fields
+fn delete_password(transaction: &sqlite::Transaction, id: &str) -> Result<(), Error> {
+    transaction.execute_params("DELETE FROM fields WHERE entry = ?", (id,))?;
+    transaction.execute_params("DELETE FROM passwords WHERE name = ?", (id,))?;
+
+    if transaction.changes() != 1 {
+        return Err(MISSING_ENTRY);
+    }
+
+    Ok(())
+}
+
+/// Set the lookup hash of the encrypted rows written before it existed
+fn fill_lookups(
+    connection: &sqlite::Connection,
+    passwords: &[Password],
+    key: &[u8; 32],
+) -> Result<(), Error> {
+    let ids = connection.query_map(
+        "SELECT name FROM passwords WHERE metadata IS NOT NULL AND lookup IS NULL",
+        (),
+        |row| row.get::<String>(0),
+    )?;
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let transaction = connection.transaction()?;
+    for password in passwords
+        .iter()
+        .filter(|password| ids.contains(&password.id))
+    {
+        // a duplicate name is left without one rather than locking the vault
+        transaction.execute_params(
+            "UPDATE OR IGNORE passwords SET lookup = ? WHERE name = ?",
+            (metadata::lookup(&password.name, key), &password.id),
+        )?;
+    }
+    transaction.commit()?;
+
+    Ok(())
+}
+
+/// An entry with this name already exists, if writing its row failed on the unique
+/// name or lookup hash
+fn duplicate(error: sqlite::Error, name: &str) -> Error {
+    if error.is_unique_violation() {
+        Error::Duplicate(name.to_string())
+    } else {
+        Error::Sqlite(error)
+    }
+}
+
+/// The row of an entry shown in the app was not found
+const MISSING_ENTRY: Error = Error::Corrupted("entry missing from the vault");
+
+/// Opaque id of an entry whose metadata is encrypted. Longer than any name, so it
+/// can't clash with the plain rows.
+fn random_id() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+/// Associated data binding a cyphertext to its entry, so rows can't be swapped or renamed
+fn associated_data(name: &str, account: &str) -> Vec<u8> {
+    let mut aad = (name.len() as u32).to_be_bytes().to_vec();
+    aad.extend_from_slice(name.as_bytes());
+    aad.extend_from_slice(account.as_bytes());
+
+    aad
+}
+
+/// Associated data for the notes of an entry. The tag can't be confused with
+/// `associated_data`, which starts with the high byte of a short length.
+fn notes_data(name: &str, account: &str) -> Vec<u8> {
+    let mut aad = b"notes\0".to_vec();
+    aad.extend_from_slice(&associated_data(name, account));
+
+    aad
+}
+
+/// Associated data for the authenticator secret of an entry, tagged like `notes_data`
+fn totp_data(name: &str, account: &str) -> Vec<u8> {
+    let mut aad = b"totp\0".to_vec();
+    aad.extend_from_slice(&associated_data(name, account));
+
+    aad
+}
+
+/// Associated data for a custom field, also binding its position, kind and name
+fn field_data(name: &str, account: &str, position: usize, kind: FieldKind, field: &str) -> Vec<u8> {
+    let mut aad = b"field\0".to_vec();
+    aad.extend_from_slice(&(position as u32).to_be_bytes());
+    aad.extend_from_slice(kind.as_str().as_bytes());
+    aad.push(0);
+    aad.extend_from_slice(&(field.len() as u32).to_be_bytes());
+    aad.extend_from_slice(field.as_bytes());
+    aad.extend_from_slice(&associated_data(name, account));
+
+    aad
+}
+
+/// Decrypt a value that was encrypted from a string
+fn decrypt_text(cyphertext: &[u8], key: &[u8; 32], aad: &[u8]) -> Result<Secret<String>, Error> {
+    let bytes = aes::decrypt(cyphertext, key, aad)?;
+
+    // reserve before pushing, so long notes don't leave a copy behind when growing
+    let mut text = Secret::text();
+    text.reserve(bytes.len());
+    // `from_utf8_lossy` only copies if there are invalid characters to replace
+    text.push_str(&String::from_utf8_lossy(&bytes));
+
+    Ok(text)
+}
+
+/// Fields of the login screen
+#[derive(Default)]
+struct Login {
+    // vault to open, and the recently opened ones to choose from
+    vault: std::path::PathBuf,
+    recent: config::Recent,
+    // whether `vault` has no master password yet, so logging in creates it
+    is_new: bool,
+    master: Secret<String>,
+    // `master` again when creating the vault
+    confirm: Secret<String>,
+    // whether `master` holds a recovery code
+    recovery: bool,
+    key_file: Option<keyfile::KeyFile>,
+    error: Option<String>,
+}
+
+impl Login {
+    /// The login screen, offering `vault` or else the most recently opened one
+    fn new(vault: Option<std::path::PathBuf>) -> Self {
+        let recent = config::Recent::load();
+        let vault = vault
+            .or_else(|| recent.vaults.first().cloned())
+            .unwrap_or_else(|| VAULT.into());
+
+        Self {
+            is_new: is_new(&vault),
+            vault,
+            recent,
+            master: Secret::text(),
+            confirm: Secret::text(),
+            recovery: false,
+            key_file: None,
+            error: None,
+        }
     }
 }
 
 enum App {
-    LoggedIn(State),
-    LoggedOut(String),
+    LoggedIn(Box<State>),
+    LoggedOut(Login),
 }
 
 impl App {
@@ -196,29 +1361,460 @@ impl App {
             style.visuals.widgets.active.corner_radius = egui::CornerRadius::same(12);
         });
 
-        Self::LoggedOut(String::new())
+        Self::LoggedOut(Login::new(None))
     }
 
-    fn login(&mut self, master: &str) {
-        *self = App::LoggedIn(State::new(master));
+    fn login(&mut self, mut form: Login) {
+        let state = if form.recovery {
+            State::recover(&form.vault, &form.master)
+        } else {
+            State::new(&form.vault, &form.master, form.key_file.as_ref())
+        };
+
+        *self = match state {
+            Ok(state) => {
+                // not being able to save the list shouldn't keep the vault locked
+                form.recent.remember(&form.vault).ok();
+                App::LoggedIn(Box::new(state))
+            }
+            Err(error) => App::LoggedOut(Login {
+                // a failed attempt may have left the vault file behind, empty
+                is_new: is_new(&form.vault),
+                master: Secret::text(),
+                confirm: Secret::text(),
+                error: Some(error.to_string()),
+                ..form
+            }),
+        };
     }
 }
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         egui::CentralPanel::default().show(ctx, |ui| match self {
-            Self::LoggedIn(state) => state.ui(ui),
-            Self::LoggedOut(master) => {
-                if let Some(master) = ui::login(master, ui) {
+            Self::LoggedIn(state) => {
+                if state.ui(ui) {
+                    // dropping the state zeroises the key
+                    let vault = state.vault.clone();
+                    *self = Self::LoggedOut(Login::new(Some(vault)));
+                }
+            }
+            Self::LoggedOut(form) => {
+                if ui::login(form, ui) {
                     // validation
-                    // `master` must be between 1 and 40 characters
-                    if master.is_empty() || master.len() > 40 {
+                    // `master` must be between 1 and 40 characters, recovery codes are
+                    // normalised later
+                    if form.master.is_empty() || (!form.recovery && form.master.len() > 40) {
                         return;
                     }
 
-                    self.login(&master);
+                    let form = std::mem::take(form);
+                    self.login(form);
                 }
             }
         });
     }
 }
+
+/// A unique path in the temporary directory, removing whatever a test created there
+/// when dropped, also if the test fails
+#[cfg(test)]
+struct TempPath(std::path::PathBuf);
+
+#[cfg(test)]
+impl TempPath {
+    fn new() -> Self {
+        let name = format!("goonpass-{}", rand::random::<u64>());
+        Self(std::env::temp_dir().join(name))
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempPath {
+    type Target = std::path::Path;
+
+    fn deref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<std::path::Path> for TempPath {
+    fn as_ref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        // a file, or a directory for tests that need several
+        if std::fs::remove_file(&self.0).is_err() {
+            std::fs::remove_dir_all(&self.0).ok();
+        }
+    }
+}
+
+#[test]
+fn test_data_key() {
+    let connection = sqlite::Connection::open(":memory:").unwrap();
+    connection.migrate(schema::MIGRATIONS).unwrap();
+
+    // a format 2 vault, with the entry encrypted under the derived key itself
+    let kdf = kdf::Kdf::Pbkdf2Sha256 { iterations: 1 };
+    let salt = [1; kdf::SALT_LEN];
+    let keys = kdf.derive(b"hunter2", &salt);
+    let aad = associated_data("mail", "me@example.com");
+
+    connection
+        .execute_params(
+            "INSERT INTO master_key (kdf, salt, verifier) VALUES (?, ?, ?)",
+            (kdf.to_string(), salt, keys.verifier),
+        )
+        .unwrap();
+    connection
+        .execute_params(
+            "INSERT INTO passwords (name, account, cyphertext) VALUES (?, ?, ?)",
+            (
+                "mail",
+                "me@example.com",
+                aes::encrypt(b"correct horse", &keys.encryption, &aad),
+            ),
+        )
+        .unwrap();
+
+    assert!(matches!(
+        unlock(&connection, PASSWORD_SLOT, "hunter3", None),
+        Err(Error::WrongPassword)
+    ));
+
+    let key = unlock(&connection, PASSWORD_SLOT, "hunter2", None)
+        .unwrap()
+        .unwrap();
+    assert_ne!(*key, *keys.encryption);
+
+    let format = setting(&connection, "format", 0).unwrap();
+    assert_eq!(format, FORMAT);
+
+    // the entry moved to the data key, which unwraps the same every time
+    let passwords = load_passwords(&connection, &key).unwrap();
+    let plaintext = aes::decrypt(&passwords[0].cyphertext, &key, &aad).unwrap();
+    assert_eq!(plaintext.as_slice(), b"correct horse");
+    assert_eq!(
+        *unlock(&connection, PASSWORD_SLOT, "hunter2", None)
+            .unwrap()
+            .unwrap(),
+        *key
+    );
+}
+
+#[test]
+fn test_recovery() {
+    let connection = sqlite::Connection::open(":memory:").unwrap();
+    connection.migrate(schema::MIGRATIONS).unwrap();
+
+    let key = random_key();
+    let kdf = kdf::Kdf::Pbkdf2Sha256 { iterations: 1 };
+    let transaction = connection.transaction().unwrap();
+    wrap_key(&transaction, PASSWORD_SLOT, kdf, "hunter2", None, &key).unwrap();
+    let code = create_recovery_code(&transaction, &key).unwrap();
+    transaction.commit().unwrap();
+
+    // both slots unwrap the same key, and codes are accepted however they are typed
+    let unlock =
+        |slot, secret: &str| unlock(&connection, slot, secret, None).map(|key| *key.unwrap());
+    assert_eq!(unlock(PASSWORD_SLOT, "hunter2").unwrap(), *key);
+    let typed = code.to_lowercase().replace('-', " ");
+    assert_eq!(
+        unlock(RECOVERY_SLOT, &recovery::normalize(&typed)).unwrap(),
+        *key
+    );
+    assert!(matches!(
+        unlock(RECOVERY_SLOT, "hunter2"),
+        Err(Error::WrongPassword)
+    ));
+
+    // a new code replaces the old one
+    let transaction = connection.transaction().unwrap();
+    let new = create_recovery_code(&transaction, &key).unwrap();
+    transaction.commit().unwrap();
+    assert!(unlock(RECOVERY_SLOT, &recovery::normalize(&code)).is_err());
+    assert_eq!(
+        unlock(RECOVERY_SLOT, &recovery::normalize(&new)).unwrap(),
+        *key
+    );
+    assert_eq!(unlock(PASSWORD_SLOT, "hunter2").unwrap(), *key);
+
+    // recovering doesn't create a vault, and one left empty by a failed login is new
+    let path = TempPath::new();
+    assert!(matches!(
+        State::recover(&path, &new),
+        Err(Error::VaultNotFound)
+    ));
+    assert!(!path.exists() && is_new(&path));
+    let connection = connect(&path).unwrap();
+    assert!(path.exists() && is_new(&path));
+    let transaction = connection.transaction().unwrap();
+    wrap_key(&transaction, PASSWORD_SLOT, kdf, "hunter2", None, &key).unwrap();
+    transaction.commit().unwrap();
+    assert!(!is_new(&path));
+}
+
+#[test]
+fn test_key_file() {
+    let connection = sqlite::Connection::open(":memory:").unwrap();
+    connection.migrate(schema::MIGRATIONS).unwrap();
+
+    let key = random_key();
+    let key_file = sha::hash(b"any bytes at all");
+    let kdf = kdf::Kdf::Pbkdf2Sha256 { iterations: 1 };
+    let transaction = connection.transaction().unwrap();
+    wrap_key(
+        &transaction,
+        PASSWORD_SLOT,
+        kdf,
+        "hunter2",
+        Some(&key_file),
+        &key,
+    )
+    .unwrap();
+    let code = create_recovery_code(&transaction, &key).unwrap();
+    transaction.commit().unwrap();
+
+    let unlock_password = |secret: &str, key_file: Option<&[u8; 32]>| {
+        unlock(&connection, PASSWORD_SLOT, secret, key_file).map(|key| *key.unwrap())
+    };
+    assert_eq!(unlock_password("hunter2", Some(&key_file)).unwrap(), *key);
+    assert!(matches!(
+        unlock_password("hunter2", None),
+        Err(Error::KeyFileRequired)
+    ));
+    assert!(matches!(
+        unlock_password("hunter2", Some(&sha::hash(b"other bytes"))),
+        Err(Error::WrongPasswordOrKeyFile)
+    ));
+    assert!(matches!(
+        unlock_password("hunter3", Some(&key_file)),
+        Err(Error::WrongPasswordOrKeyFile)
+    ));
+
+    // the recovery code works without the key file
+    let recovered = unlock(
+        &connection,
+        RECOVERY_SLOT,
+        &recovery::normalize(&code),
+        None,
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(*recovered, *key);
+
+    // and a vault without a key file refuses one
+    let transaction = connection.transaction().unwrap();
+    wrap_key(&transaction, PASSWORD_SLOT, kdf, "hunter2", None, &key).unwrap();
+    transaction.commit().unwrap();
+    assert!(matches!(
+        unlock_password("hunter2", Some(&key_file)),
+        Err(Error::KeyFileNotUsed)
+    ));
+}
+
+#[test]
+fn test_entries() {
+    let connection = sqlite::Connection::open(":memory:").unwrap();
+    connection.migrate(schema::MIGRATIONS).unwrap();
+    let mut state =
+        State::open(std::path::Path::new(":memory:"), connection, random_key()).unwrap();
+
+    // secrets are no longer limited to 40 characters
+    let long = "correct horse battery staple ".repeat(4);
+    state.name_field.push_str("mail");
+    state.account_field.push_str("me@example.com");
+    state.plaintext_field.push_str(&long);
+    state.url_field.push_str(" https://mail.example.com ");
+    state.add_password().unwrap();
+
+    let password = state.passwords[0].clone();
+    assert_eq!(password.url, "https://mail.example.com");
+
+    // but still to what fits in the buffer of `Secret::text`
+    let longest = "🔑".repeat(secret::TEXT_LIMIT);
+    assert!(valid("mail", "me@example.com", &longest));
+    assert!(!valid("mail", "me@example.com", &format!("{longest}a")));
+
+    // the schema keeps names unique
+    state.name_field.push_str("mail");
+    state.account_field.push_str("other@example.com");
+    state.plaintext_field.push_str("hunter2");
+    assert!(matches!(state.add_password(), Err(Error::Duplicate(name)) if name == "mail"));
+    assert_eq!(
+        load_passwords(&state.connection, &state.key).unwrap().len(),
+        1
+    );
+    assert!(password.created.is_some() && password.last_used.is_none());
+
+    state.start_edit(&password).unwrap();
+    let edit = state.edit.as_mut().unwrap();
+    assert_eq!(edit.plaintext_field.as_str(), long);
+    edit.name_field = "email".to_string();
+    edit.notes_field.push_str("first line\nsecond line");
+    edit.totp_field.push_str("not base32!");
+    for (kind, name, value) in [
+        (FieldKind::Hidden, "PIN", "1234"),
+        (FieldKind::Url, "Recovery", "https://example.com/recover"),
+    ] {
+        let mut field = EditField {
+            kind,
+            name: name.to_string(),
+            value: Secret::text(),
+        };
+        field.value.push_str(value);
+        edit.fields.push(field);
+    }
+    assert!(matches!(state.save_edit(), Err(Error::InvalidTotp)));
+    let edit = state.edit.as_mut().unwrap();
+    edit.totp_field.clear();
+    edit.totp_field
+        .push_str("GEZD GNBV GY3T QOJQ GEZD GNBV GY3T QOJQ");
+    state.save_edit().unwrap();
+
+    // everything survives a reload, and the renamed entry has no stale fields
+    let passwords = load_passwords(&state.connection, &state.key).unwrap();
+    assert_eq!(passwords.len(), 1);
+    let password = &passwords[0];
+    assert_eq!(password.name, "email");
+    assert_eq!(
+        state.decrypt_notes(password).unwrap().as_str(),
+        "first line\nsecond line"
+    );
+    let fields = state.decrypt_fields(password).unwrap();
+    assert_eq!(password.fields[0].name, "PIN");
+    assert!(password.fields[0].kind == FieldKind::Hidden);
+    assert_eq!(fields[0].as_str(), "1234");
+    assert_eq!(fields[1].as_str(), "https://example.com/recover");
+    let totp = state.decrypt_totp(password).unwrap().unwrap();
+    assert_eq!(totp.code(59).as_str(), "287082");
+
+    // using an entry rewrites its time and leaves the rest of the row alone
+    let mut used = password.clone();
+    used.last_used = Some(1_700_000_000);
+    touch_password(&state.connection, &used, None).unwrap();
+    let reloaded = load_passwords(&state.connection, &state.key).unwrap();
+    assert_eq!(reloaded[0].last_used, Some(1_700_000_000));
+    assert_eq!(reloaded[0].fields.len(), 2);
+    used.id = "missing".to_string();
+    assert!(touch_password(&state.connection, &used, None).is_err());
+
+    // field values are bound to their position
+    let mut swapped = password.clone();
+    swapped.fields.swap(0, 1);
+    assert!(state.decrypt_fields(&swapped).is_err());
+
+    state.remove_password(0, password).unwrap();
+    assert!(state.remove_password(0, password).is_err());
+    let orphans = state
+        .connection
+        .query_row("SELECT COUNT(*) FROM fields", (), |row| row.get::<i64>(0))
+        .unwrap();
+    assert_eq!(orphans, Some(0));
+}
+
+#[test]
+fn test_metadata() {
+    let connection = connect(std::path::Path::new(":memory:")).unwrap();
+    let secure_delete = connection
+        .query_row("PRAGMA secure_delete", (), |row| row.get::<i64>(0))
+        .unwrap();
+    assert_eq!(secure_delete, Some(1));
+    let mut state =
+        State::open(std::path::Path::new(":memory:"), connection, random_key()).unwrap();
+
+    state.set_encrypt_metadata(true).unwrap();
+    for name in ["mail", "bank", "work"] {
+        state.name_field.push_str(name);
+        state.account_field.push_str("me@example.com");
+        state.plaintext_field.push_str("hunter2");
+        state.url_field.push_str("https://example.com");
+        state.add_password().unwrap();
+    }
+
+    // nothing but opaque ids in the clear
+    let rows = |state: &State| {
+        state
+            .connection
+            .query_map("SELECT name, account, url FROM passwords", (), |row| {
+                row.get::<(String, String, String)>(0)
+            })
+            .unwrap()
+    };
+    for (id, account, url) in rows(&state) {
+        assert_eq!(id.len(), 32);
+        assert!(account.is_empty() && url.is_empty());
+    }
+
+    // names stay unique through their lookup hash, also when renaming
+    state.name_field.push_str("mail");
+    state.account_field.push_str("other@example.com");
+    state.plaintext_field.push_str("hunter2");
+    assert!(matches!(state.add_password(), Err(Error::Duplicate(_))));
+    let bank = state.passwords[0].clone();
+    state.start_edit(&bank).unwrap();
+    state.edit.as_mut().unwrap().name_field = "work".to_string();
+    assert!(matches!(state.save_edit(), Err(Error::Duplicate(_))));
+    state.edit = None;
+    assert_eq!(rows(&state).len(), 3);
+
+    // rows encrypted before the lookup hash existed get one on open
+    state
+        .connection
+        .execute("UPDATE passwords SET lookup = NULL")
+        .unwrap();
+    fill_lookups(&state.connection, &state.passwords, &state.key).unwrap();
+    let missing = state
+        .connection
+        .query_row(
+            "SELECT COUNT(*) FROM passwords WHERE lookup IS NULL",
+            (),
+            |row| row.get::<i64>(0),
+        )
+        .unwrap();
+    assert_eq!(missing, Some(0));
+
+    // decrypted and sorted on load, and usable like plain entries
+    let passwords = load_passwords(&state.connection, &state.key).unwrap();
+    let names: Vec<_> = passwords
+        .iter()
+        .map(|password| password.name.as_str())
+        .collect();
+    assert_eq!(names, ["bank", "mail", "work"]);
+    assert_eq!(passwords[1].url, "https://example.com");
+
+    let password = passwords[1].clone();
+    state.start_edit(&password).unwrap();
+    state.edit.as_mut().unwrap().name_field = "email".to_string();
+    state.save_edit().unwrap();
+    let passwords = load_passwords(&state.connection, &state.key).unwrap();
+    assert_eq!(passwords[1].name, "email");
+    assert_eq!(passwords[1].id, password.id);
+    let aad = associated_data("email", "me@example.com");
+    let plaintext = aes::decrypt(&passwords[1].cyphertext, &state.key, &aad).unwrap();
+    assert_eq!(plaintext.as_slice(), b"hunter2");
+
+    let mut used = passwords[1].clone();
+    used.last_used = Some(1_700_000_000);
+    touch_password(&state.connection, &used, Some(&state.key)).unwrap();
+    let passwords = load_passwords(&state.connection, &state.key).unwrap();
+    assert_eq!(passwords[1].name, "email");
+    assert_eq!(passwords[1].last_used, Some(1_700_000_000));
+
+    // and back in the clear
+    state.set_encrypt_metadata(false).unwrap();
+    let mut rows = rows(&state);
+    rows.sort();
+    assert_eq!(rows[0].0, "bank");
+    assert_eq!(rows[1].1, "me@example.com");
+    assert_eq!(
+        load_passwords(&state.connection, &state.key).unwrap().len(),
+        3
+    );
+}
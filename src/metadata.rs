@@ -0,0 +1,165 @@
+//! Entry metadata encrypted as a single blob, so the vault doesn't reveal which sites
+//! and accounts it holds. The row then only keeps an opaque id, the encrypted password,
+//! notes and field values, whose associated data still binds them to the real name.
+
+use crate::{Field, FieldKind, Password, aes, error::Error, kdf, sha};
+
+/// Encrypt the name, account, website, times and custom field names of an entry,
+/// bound to its row id
+pub fn encrypt(password: &Password, key: &[u8; 32]) -> Vec<u8> {
+    let mut plaintext = Vec::new();
+    string(&mut plaintext, &password.name);
+    string(&mut plaintext, &password.account);
+    string(&mut plaintext, &password.url);
+    for time in [password.created, password.modified, password.last_used] {
+        plaintext.extend_from_slice(&time.unwrap_or(i64::MIN).to_be_bytes());
+    }
+
+    plaintext.extend_from_slice(&(password.fields.len() as u32).to_be_bytes());
+    for field in &password.fields {
+        string(&mut plaintext, field.kind.as_str());
+        string(&mut plaintext, &field.name);
+    }
+
+    aes::encrypt(&plaintext, key, &associated_data(&password.id))
+}
+
+/// Decrypt the metadata of the row `id`, the cyphertexts of the password and custom
+/// fields are left empty for the caller to fill in
+pub fn decrypt(id: &str, cyphertext: &[u8], key: &[u8; 32]) -> Result<Password, Error> {
+    let plaintext = aes::decrypt(cyphertext, key, &associated_data(id))?;
+    let mut reader = Reader(&plaintext);
+
+    let name = reader.string()?;
+    let account = reader.string()?;
+    let url = reader.string()?;
+    let [created, modified, last_used] = [(); 3].map(|()| reader.time());
+
+    let count = reader.u32()?;
+    let mut fields = Vec::new();
+    for _ in 0..count {
+        let kind = FieldKind::parse(&reader.string()?).ok_or(INVALID)?;
+        fields.push(Field {
+            kind,
+            name: reader.string()?,
+            cyphertext: Vec::new(),
+        });
+    }
+
+    if !reader.0.is_empty() {
+        return Err(INVALID);
+    }
+
+    Ok(Password {
+        id: id.to_string(),
+        name,
+        account,
+        url,
+        fields,
+        created: created?,
+        modified: modified?,
+        last_used: last_used?,
+        ..Password::default()
+    })
+}
+
+/// Keyed hash of an entry name, stored in the clear so the vault can keep names unique
+/// without revealing them
+pub fn lookup(name: &str, key: &[u8; 32]) -> [u8; 32] {
+    sha::hmac(&*kdf::subkey(key, b"goonpass lookup"), name.as_bytes())
+}
+
+const INVALID: Error = Error::Corrupted("invalid entry metadata");
+
+/// Associated data binding the metadata to its row. The tag can't be confused with
+/// the other associated data of an entry.
+fn associated_data(id: &str) -> Vec<u8> {
+    let mut aad = b"metadata\0".to_vec();
+    aad.extend_from_slice(id.as_bytes());
+
+    aad
+}
+
+/// Append a length-prefixed string
+fn string(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let (bytes, rest) = self.0.split_first_chunk().ok_or(INVALID)?;
+        self.0 = rest;
+
+        Ok(*bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        self.bytes().map(u32::from_be_bytes)
+    }
+
+    fn time(&mut self) -> Result<Option<i64>, Error> {
+        let time = i64::from_be_bytes(self.bytes()?);
+
+        Ok((time != i64::MIN).then_some(time))
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let len = self.u32()? as usize;
+        if len > self.0.len() {
+            return Err(INVALID);
+        }
+
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+
+        String::from_utf8(bytes.to_vec()).map_err(|_| INVALID)
+    }
+}
+
+#[test]
+fn test() {
+    let key = [7; 32];
+    let password = Password {
+        id: "0123abcd".to_string(),
+        name: "mail".to_string(),
+        account: "me@example.com".to_string(),
+        cyphertext: vec![1, 2, 3],
+        url: "https://mail.example.com".to_string(),
+        fields: vec![Field {
+            kind: FieldKind::Hidden,
+            name: "PIN".to_string(),
+            cyphertext: vec![4, 5, 6],
+        }],
+        created: Some(1_700_000_000),
+        modified: Some(1_700_000_100),
+        last_used: None,
+        ..Password::default()
+    };
+
+    let cyphertext = encrypt(&password, &key);
+    let windows = |needle: &[u8]| cyphertext.windows(needle.len()).any(|w| w == needle);
+    assert!(!windows(b"mail") && !windows(b"PIN"));
+
+    let decrypted = decrypt("0123abcd", &cyphertext, &key).unwrap();
+    assert_eq!(decrypted.id, password.id);
+    assert_eq!(decrypted.name, password.name);
+    assert_eq!(decrypted.account, password.account);
+    assert_eq!(decrypted.url, password.url);
+    assert_eq!(decrypted.created, password.created);
+    assert_eq!(decrypted.modified, password.modified);
+    assert_eq!(decrypted.last_used, None);
+    assert_eq!(decrypted.fields.len(), 1);
+    assert!(decrypted.fields[0].kind == FieldKind::Hidden);
+    assert_eq!(decrypted.fields[0].name, "PIN");
+    assert!(decrypted.fields[0].cyphertext.is_empty());
+
+    // bound to the row
+    assert!(decrypt("4567cdef", &cyphertext, &key).is_err());
+
+    assert_eq!(lookup("mail", &key), lookup("mail", &key));
+    assert_ne!(lookup("mail", &key), lookup("Mail", &key));
+    assert_ne!(lookup("mail", &key), lookup("mail", &[8; 32]));
+}
@@ -0,0 +1,68 @@
+//! Recovery codes, shown once at vault creation to unlock the vault if the master
+//! password is forgotten
+//!
+//! A code is 160 random bits in Crockford's base32, grouped like `7K3M-QX0A-...`.
+//! That is too much entropy to guess, so unlike the master password it isn't stretched.
+
+use crate::{kdf, secret::Secret};
+
+/// Crockford's base32 alphabet, without `I`, `L`, `O` and `U`
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const BYTES: usize = 20;
+const GROUP: usize = 4;
+
+/// A single iteration, see the module documentation
+pub const KDF: kdf::Kdf = kdf::Kdf::Pbkdf2Sha256 { iterations: 1 };
+
+/// Generate a new code, formatted for display
+pub fn generate() -> Secret<String> {
+    let mut bytes = Secret::new([0_u8; BYTES]);
+    rand::fill(&mut *bytes);
+
+    let mut code = Secret::text();
+    let mut buffer = 0_u16;
+    let mut bits = 0;
+    for &byte in bytes.iter() {
+        buffer = (buffer << 8) | u16::from(byte);
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            if !code.is_empty() && code.len() % (GROUP + 1) == GROUP {
+                code.push('-');
+            }
+            code.push(ALPHABET[usize::from((buffer >> bits) & 0x1F)] as char);
+        }
+    }
+
+    code
+}
+
+/// Canonical form of a code as typed by the user: uppercase, without separators,
+/// and with the letters Crockford's base32 leaves out read as the digits they resemble
+pub fn normalize(code: &str) -> Secret<String> {
+    let mut normalized = Secret::text();
+
+    for c in code.chars() {
+        match c.to_ascii_uppercase() {
+            '-' | ' ' => {}
+            'O' => normalized.push('0'),
+            'I' | 'L' => normalized.push('1'),
+            c => normalized.push(c),
+        }
+    }
+
+    normalized
+}
+
+#[test]
+fn test() {
+    let code = generate();
+    assert_eq!(code.len(), 32 + 7);
+    assert!(code.split('-').all(|group| group.len() == GROUP));
+    assert!(code.bytes().all(|c| c == b'-' || ALPHABET.contains(&c)));
+    assert_ne!(*code, *generate());
+
+    assert_eq!(*normalize(&code), code.replace('-', ""));
+    assert_eq!(*normalize("7k3m-qxoa il"), *normalize("7K3MQX0A11"));
+}
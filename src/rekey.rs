@@ -0,0 +1,115 @@
+//! Changing the master password, on a background thread since deriving the old and
+//! new keys takes a few seconds. Only the wrapped data key is rewritten, the entries
+//! stay encrypted under the same data key.
+
+use std::{path::PathBuf, sync::mpsc, thread};
+
+use eframe::egui;
+
+use crate::{PASSWORD_SLOT, WrappedKey, connect, error::Error, kdf, secret::Secret, unlock};
+
+pub enum Progress {
+    /// Deriving the old key to check the current password
+    Verifying,
+    /// Deriving the new key
+    Deriving,
+}
+
+pub struct Rekey {
+    pub progress: Progress,
+    receiver: mpsc::Receiver<Progress>,
+    handle: Option<thread::JoinHandle<Result<(), Error>>>,
+}
+
+impl Rekey {
+    /// Start changing the master password from `old` to `new`, both combined with the
+    /// hash of the key file if the vault uses one
+    pub fn start(
+        vault: PathBuf,
+        old: Secret<String>,
+        new: Secret<String>,
+        key_file: Option<Secret<[u8; 32]>>,
+        ctx: &egui::Context,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+
+        let handle = thread::spawn(move || {
+            let kdf = kdf::Kdf::default();
+            rekey(&vault, &old, &new, key_file.as_deref(), kdf, |progress| {
+                sender.send(progress).ok();
+                ctx.request_repaint();
+            })
+        });
+
+        Self {
+            progress: Progress::Verifying,
+            receiver,
+            handle: Some(handle),
+        }
+    }
+
+    /// Update the progress, returning the result once finished
+    pub fn poll(&mut self) -> Option<Result<(), Error>> {
+        while let Ok(progress) = self.receiver.try_recv() {
+            self.progress = progress;
+        }
+
+        if !self.handle.as_ref()?.is_finished() {
+            return None;
+        }
+
+        let result = self.handle.take()?.join();
+        Some(result.unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+    }
+}
+
+/// Unwrap the data key with `old` and wrap it again under `new`, derived with `kdf`
+fn rekey(
+    vault: &std::path::Path,
+    old: &str,
+    new: &str,
+    key_file: Option<&[u8; 32]>,
+    kdf: kdf::Kdf,
+    progress: impl Fn(Progress),
+) -> Result<(), Error> {
+    // a separate connection, `sqlite::Connection` can't be shared between threads
+    let connection = connect(vault)?;
+
+    progress(Progress::Verifying);
+    let key = unlock(&connection, PASSWORD_SLOT, old, key_file)?
+        .ok_or(Error::Corrupted("missing master key"))?;
+
+    progress(Progress::Deriving);
+    let wrapped = WrappedKey::new(kdf, new, key_file, &key);
+
+    // both keys are derived, so the app is only locked out of writing for the update
+    let transaction = connection.transaction()?;
+    wrapped.store(&transaction, PASSWORD_SLOT)?;
+    transaction.commit()?;
+
+    Ok(())
+}
+
+#[test]
+fn test() {
+    let path = crate::TempPath::new();
+    let connection = connect(&path).unwrap();
+    let key = crate::random_key();
+    let transaction = connection.transaction().unwrap();
+    let kdf = kdf::Kdf::Pbkdf2Sha256 { iterations: 1 };
+    crate::wrap_key(&transaction, PASSWORD_SLOT, kdf, "hunter2", None, &key).unwrap();
+    transaction.commit().unwrap();
+
+    let wrong = rekey(&path, "hunter3", "hunter4", None, kdf, |_| {});
+    assert!(matches!(wrong, Err(Error::WrongPassword)));
+
+    // the app can still write while the keys are being derived
+    rekey(&path, "hunter2", "hunter4", None, kdf, |_| {
+        crate::set_setting(&connection, "lock_after", 1).unwrap();
+    })
+    .unwrap();
+
+    let unlocked = unlock(&connection, PASSWORD_SLOT, "hunter4", None).unwrap();
+    assert_eq!(*unlocked.unwrap(), *key);
+}
@@ -0,0 +1,129 @@
+//! Vault schema, as an ordered list of migrations applied by `sqlite::Connection::migrate`.
+//! Never edit a released migration, append a new one instead.
+
+pub const MIGRATIONS: &[&str] = &[
+    // 1: the tables as they existed before the schema was versioned
+    "CREATE TABLE IF NOT EXISTS passwords (name STRING PRIMARY KEY, account STRING, cyphertext BLOB);
+     CREATE TABLE IF NOT EXISTS master_key (kdf STRING, salt BLOB, verifier BLOB);
+     CREATE TABLE IF NOT EXISTS meta (key STRING PRIMARY KEY, value STRING);",
+    // 2: `STRING` has numeric affinity, so names like `1234` were stored as integers,
+    // and cyphertexts were bound as text
+    "CREATE TABLE passwords_new (
+         name TEXT PRIMARY KEY NOT NULL,
+         account TEXT NOT NULL,
+         cyphertext BLOB NOT NULL
+     );
+     INSERT INTO passwords_new
+         SELECT CAST(name AS TEXT), CAST(account AS TEXT), CAST(cyphertext AS BLOB) FROM passwords;
+     DROP TABLE passwords;
+     ALTER TABLE passwords_new RENAME TO passwords;
+
+     CREATE TABLE master_key_new (kdf TEXT NOT NULL, salt BLOB NOT NULL, verifier BLOB NOT NULL);
+     INSERT INTO master_key_new
+         SELECT CAST(kdf AS TEXT), CAST(salt AS BLOB), CAST(verifier AS BLOB) FROM master_key;
+     DROP TABLE master_key;
+     ALTER TABLE master_key_new RENAME TO master_key;
+
+     CREATE TABLE meta_new (key TEXT PRIMARY KEY NOT NULL, value TEXT NOT NULL);
+     INSERT INTO meta_new SELECT CAST(key AS TEXT), CAST(value AS TEXT) FROM meta;
+     DROP TABLE meta;
+     ALTER TABLE meta_new RENAME TO meta;",
+    // 3: the data key, wrapped under the key derived from the master password
+    "ALTER TABLE master_key ADD COLUMN wrapped_key BLOB;",
+    // 4: key slots, the master password and the recovery code each wrap the data key
+    "ALTER TABLE master_key ADD COLUMN slot TEXT NOT NULL DEFAULT 'password';",
+    // 5: whether the slot's secret is combined with a key file
+    "ALTER TABLE master_key ADD COLUMN key_file INTEGER NOT NULL DEFAULT 0;",
+    // 6: richer entries, notes and field values are encrypted like the password,
+    // and times are in unix seconds
+    "ALTER TABLE passwords ADD COLUMN url TEXT NOT NULL DEFAULT '';
+     ALTER TABLE passwords ADD COLUMN notes BLOB;
+     ALTER TABLE passwords ADD COLUMN created INTEGER;
+     ALTER TABLE passwords ADD COLUMN modified INTEGER;
+     ALTER TABLE passwords ADD COLUMN last_used INTEGER;
+
+     CREATE TABLE fields (
+         entry TEXT NOT NULL,
+         position INTEGER NOT NULL,
+         kind TEXT NOT NULL,
+         name TEXT NOT NULL,
+         cyphertext BLOB NOT NULL,
+         PRIMARY KEY (entry, position)
+     );",
+    // 7: entry metadata encrypted as one blob, the row is then keyed by an opaque id
+    "ALTER TABLE passwords ADD COLUMN metadata BLOB;",
+    // 8: authenticator secret, encrypted like the password
+    "ALTER TABLE passwords ADD COLUMN totp BLOB;",
+    // 9: keyed hash of the name of an entry whose metadata is encrypted, so names stay
+    // unique without being readable. Plain rows are already unique by name.
+    "ALTER TABLE passwords ADD COLUMN lookup BLOB;
+     CREATE UNIQUE INDEX passwords_lookup ON passwords (lookup);",
+];
+
+#[test]
+fn test() {
+    use crate::sqlite;
+
+    let path = crate::TempPath::new();
+    let path = path.to_str().unwrap();
+
+    // a `db` file as written before the schema was versioned
+    {
+        let connection = sqlite::Connection::open(path).unwrap();
+        connection
+            .execute(
+                "CREATE TABLE passwords (name STRING PRIMARY KEY, account STRING, cyphertext BLOB);
+                 CREATE TABLE master (hash BLOB, salt BLOB);
+                 INSERT INTO master VALUES (x'00112233', x'01020304');
+                 INSERT INTO passwords VALUES ('1234', 'me@example.com', CAST(x'DEADBEEF' AS TEXT));
+                 INSERT INTO passwords VALUES ('mail', 'me@example.com', x'CAFE');",
+            )
+            .unwrap();
+        assert_eq!(connection.user_version().unwrap(), 0);
+    }
+
+    let connection = sqlite::Connection::open(path).unwrap();
+    connection.migrate(MIGRATIONS).unwrap();
+    assert_eq!(connection.user_version().unwrap(), MIGRATIONS.len() as u32);
+
+    let entries = connection
+        .query_map(
+            "SELECT name, typeof(name), typeof(cyphertext), cyphertext FROM passwords ORDER BY name",
+            (),
+            |row| row.get::<(String, String, String, Vec<u8>)>(0),
+        )
+        .unwrap();
+    assert_eq!(
+        entries,
+        [
+            (
+                "1234".to_string(),
+                "text".to_string(),
+                "blob".to_string(),
+                vec![0xDE, 0xAD, 0xBE, 0xEF]
+            ),
+            (
+                "mail".to_string(),
+                "text".to_string(),
+                "blob".to_string(),
+                vec![0xCA, 0xFE]
+            ),
+        ]
+    );
+
+    // the legacy key is left for `legacy::migrate`, which needs the master password
+    let hash = connection
+        .query_row("SELECT hash FROM master", (), |row| row.get::<Vec<u8>>(0))
+        .unwrap();
+    assert_eq!(hash, Some(vec![0x00, 0x11, 0x22, 0x33]));
+
+    assert!(
+        connection
+            .execute("INSERT INTO passwords (name) VALUES ('bank')")
+            .is_err()
+    );
+
+    // running again is a no-op, and newer schemas are refused
+    connection.migrate(MIGRATIONS).unwrap();
+    assert!(connection.migrate(&MIGRATIONS[..1]).is_err());
+}
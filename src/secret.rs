@@ -0,0 +1,124 @@
+//! Keys and plaintext that are wiped from memory when dropped
+
+use std::ops::{Deref, DerefMut};
+
+/// Longest text, in characters, that the fields holding secrets accept
+pub const TEXT_LIMIT: usize = 1024;
+
+/// Capacity of [`Secret::text`], so typing up to the limit doesn't reallocate. A
+/// character takes up to 4 bytes in UTF-8.
+const TEXT_CAPACITY: usize = TEXT_LIMIT * 4;
+
+/// Overwrite a value with zeros, in a way the compiler can't optimise away
+pub trait Wipe {
+    fn wipe(&mut self);
+}
+
+impl<T: Copy + Default, const N: usize> Wipe for [T; N] {
+    fn wipe(&mut self) {
+        for element in self.iter_mut() {
+            unsafe { std::ptr::write_volatile(element, T::default()) };
+        }
+        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+impl<T: Copy + Default> Wipe for Vec<T> {
+    fn wipe(&mut self) {
+        // the spare capacity may still hold elements that were truncated
+        let len = self.len();
+        self.resize(self.capacity(), T::default());
+        for element in self.iter_mut() {
+            unsafe { std::ptr::write_volatile(element, T::default()) };
+        }
+        self.truncate(len);
+        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+impl Wipe for String {
+    fn wipe(&mut self) {
+        // zeros are valid UTF-8
+        unsafe { self.as_mut_vec() }.wipe();
+    }
+}
+
+/// Wraps a value that is wiped when dropped
+///
+/// There is deliberately no `Clone`, every copy would be another thing to wipe,
+/// and `Debug` never prints the contents. Note that growing a `Vec` or `String`
+/// past its capacity moves it, leaving the old allocation unwiped.
+pub struct Secret<T: Wipe>(T);
+
+impl<T: Wipe> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl Secret<String> {
+    /// An empty string with room for any text within [`TEXT_LIMIT`]
+    pub fn text() -> Self {
+        Self(String::with_capacity(TEXT_CAPACITY))
+    }
+}
+
+impl Default for Secret<String> {
+    fn default() -> Self {
+        Self::text()
+    }
+}
+
+impl<T: Wipe> Deref for Secret<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Wipe> DerefMut for Secret<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Wipe> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.wipe();
+    }
+}
+
+impl<T: Wipe> std::fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Secret(..)")
+    }
+}
+
+#[test]
+fn test() {
+    let mut key = [0xAB_u8; 32];
+    key.wipe();
+    assert_eq!(key, [0; 32]);
+
+    let mut buffer = b"correct horse battery staple".to_vec();
+    buffer.truncate(7);
+    buffer.wipe();
+    assert_eq!(buffer, [0; 7]);
+    // the truncated bytes are wiped too
+    unsafe { buffer.set_len(28) };
+    assert_eq!(buffer, [0; 28]);
+
+    let mut text = Secret::text();
+    text.push_str("hunter2");
+    assert_eq!(format!("{text:?}"), "Secret(..)");
+    assert_eq!(text.as_str(), "hunter2");
+    text.wipe();
+    assert_eq!(text.as_str(), "\0\0\0\0\0\0\0");
+
+    // typing up to the limit stays in the buffer that gets wiped
+    let mut text = Secret::text();
+    let buffer = text.as_ptr();
+    text.extend(std::iter::repeat_n('🔑', TEXT_LIMIT));
+    assert_eq!(text.as_ptr(), buffer);
+}
@@ -1,48 +1,159 @@
+use crate::secret::Secret;
+
+/// Initial hash value of SHA-256 (FIPS 180-4 section 5.3.3)
+const H: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
 pub fn hash(message: &[u8]) -> [u8; 32] {
-    let mut hash = [
-        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
-        0x5be0cd19,
-    ];
+    let mut sha = Sha256::new();
+    sha.update(message);
+
+    sha.finalize()
+}
+
+/// SHA-256 over a message fed in pieces, so files can be hashed without reading them
+/// into memory. The buffered input may be a password or key and is wiped on drop.
+pub struct Sha256 {
+    state: Secret<[u32; 8]>,
+    buffer: Secret<[u8; 64]>,
+    /// Bytes waiting in `buffer` for a full block
+    buffered: usize,
+    /// Length of the whole message so far
+    len: u64,
+}
 
-    let (chunks, remainder) = message.as_chunks::<64>();
-    for block in chunks {
-        hash_block(block, &mut hash);
+impl Sha256 {
+    pub fn new() -> Self {
+        Self {
+            state: Secret::new(H),
+            buffer: Secret::new([0; 64]),
+            buffered: 0,
+            len: 0,
+        }
     }
 
-    let len = remainder.len();
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
 
-    if len == 0 {
-        let mut block = [0; 64];
-        block[55] = 0x80;
-        hash_block(&block, &mut hash);
-    } else if len < 56 {
-        let mut block = [0; 64];
-        block[..len].copy_from_slice(remainder);
-        block[len] = 0x80;
-        block[56..].copy_from_slice(&(len as u64 * 8).to_be_bytes());
+        if self.buffered > 0 {
+            let take = data.len().min(64 - self.buffered);
+            self.buffer[self.buffered..self.buffered + take].copy_from_slice(&data[..take]);
+            self.buffered += take;
+            data = &data[take..];
 
-        hash_block(&block, &mut hash);
-    } else if len == 56 {
-        let mut block = [0; 64];
-        block[..len].copy_from_slice(remainder);
-        block[len] = 0x80;
-        hash_block(&block, &mut hash);
+            if self.buffered < 64 {
+                return;
+            }
+            hash_block(&self.buffer, &mut self.state);
+            self.buffered = 0;
+        }
 
-        let mut block = [0; 64];
-        block[56..].copy_from_slice(&(len as u64 * 8).to_be_bytes());
-        hash_block(&block, &mut hash);
+        let (chunks, remainder) = data.as_chunks::<64>();
+        for block in chunks {
+            hash_block(block, &mut self.state);
+        }
+
+        self.buffer[..remainder.len()].copy_from_slice(remainder);
+        self.buffered = remainder.len();
+    }
+
+    pub fn finalize(mut self) -> [u8; 32] {
+        // the length field covers the whole message, not just the final block
+        let bits = (self.len * 8).to_be_bytes();
+
+        let len = self.buffered;
+        self.buffer[len] = 0x80;
+        self.buffer[len + 1..].fill(0);
+
+        // no room left for the length, it goes in a block of its own
+        if len >= 56 {
+            hash_block(&self.buffer, &mut self.state);
+            self.buffer.fill(0);
+        }
+
+        self.buffer[56..].copy_from_slice(&bits);
+        hash_block(&self.buffer, &mut self.state);
+
+        digest(&self.state)
     }
+}
 
+fn digest(state: &[u32; 8]) -> [u8; 32] {
     let mut digest = [0; 32];
-    for (i, word) in digest.chunks_exact_mut(4).enumerate() {
-        word.copy_from_slice(&hash[i].to_be_bytes());
+    for (word, value) in digest.chunks_exact_mut(4).zip(state) {
+        word.copy_from_slice(&value.to_be_bytes());
     }
 
     digest
 }
 
+/// HMAC-SHA256 (RFC 2104)
+pub fn hmac(key: &[u8], message: &[u8]) -> [u8; 32] {
+    hmac_with(hash, 64, key, message)
+}
+
+/// HMAC (RFC 2104) over any hash, given the size of its input blocks, which the key
+/// is padded to
+pub fn hmac_with<const N: usize>(
+    hash: impl Fn(&[u8]) -> [u8; N],
+    block_len: usize,
+    key: &[u8],
+    message: &[u8],
+) -> [u8; N] {
+    let mut block = Secret::new(vec![0; block_len]);
+    if key.len() > block_len {
+        block[..N].copy_from_slice(&*Secret::new(hash(key)));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    // sized up front, growing would leave an unwiped copy of the padded key behind
+    let mut inner = Secret::new(Vec::with_capacity(block_len + message.len()));
+    inner.extend(block.iter().map(|b| b ^ 0x36));
+    inner.extend_from_slice(message);
+    let inner = Secret::new(hash(&inner));
+
+    let mut outer = Secret::new(Vec::with_capacity(block_len + N));
+    outer.extend(block.iter().map(|b| b ^ 0x5c));
+    outer.extend_from_slice(&*inner);
+
+    hash(&outer)
+}
+
+/// HKDF-Extract with SHA-256 (RFC 5869), concentrating the entropy of `ikm` into a
+/// pseudorandom key. An empty salt stands for a block of zeros, as the RFC specifies.
+pub fn hkdf_extract(salt: &[u8], ikm: &[u8]) -> Secret<[u8; 32]> {
+    Secret::new(hmac(salt, ikm))
+}
+
+/// HKDF-Expand with SHA-256 (RFC 5869), filling `output` with key material bound to
+/// `info`, so one pseudorandom key yields independent keys for different purposes
+pub fn hkdf_expand(prk: &[u8], info: &[u8], output: &mut [u8]) {
+    assert!(
+        output.len() <= 255 * 32,
+        "HKDF output is limited to 255 blocks"
+    );
+
+    let mut message = Secret::new(Vec::with_capacity(32 + info.len() + 1));
+    let mut block = Secret::new([0; 32]);
+
+    for (counter, chunk) in (1..=255_u8).zip(output.chunks_mut(32)) {
+        // T(i) = HMAC(PRK, T(i - 1) | info | i), with an empty T(0)
+        message.clear();
+        if counter > 1 {
+            message.extend_from_slice(&*block);
+        }
+        message.extend_from_slice(info);
+        message.push(counter);
+
+        *block = hmac(prk, &message);
+        chunk.copy_from_slice(&block[..chunk.len()]);
+    }
+}
+
 fn hash_block(block: &[u8; 64], hash: &mut [u32; 8]) {
-    let mut w = [0_u32; 64];
+    let mut w = Secret::new([0_u32; 64]);
 
     for i in 0..16 {
         w[i] = ((block[i * 4 + 0] as u32) << 24)
@@ -96,6 +207,135 @@ fn hash_block(block: &[u8; 64], hash: &mut [u32; 8]) {
     hash[7] = hash[7].wrapping_add(h);
 }
 
+/// SHA-1 (FIPS 180-4), broken for signatures but still fine in HMAC, where TOTP
+/// secrets mostly use it
+pub fn sha1(message: &[u8]) -> [u8; 20] {
+    let mut hash: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+    let padded = padded::<64>(message, 8);
+    for block in padded.as_chunks::<64>().0 {
+        let mut w = Secret::new([0_u32; 80]);
+        for (i, word) in block.as_chunks::<4>().0.iter().enumerate() {
+            w[i] = u32::from_be_bytes(*word);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = hash;
+        for (round, &w) in w.iter().enumerate() {
+            let (f, k) = match round {
+                0..20 => (ch(b, c, d), 0x5a827999),
+                20..40 => (b ^ c ^ d, 0x6ed9eba1),
+                40..60 => (maj(b, c, d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+
+            let t = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(w);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+
+        for (h, v) in hash.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(hash) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+
+    digest
+}
+
+/// SHA-512 (FIPS 180-4)
+pub fn sha512(message: &[u8]) -> [u8; 64] {
+    let mut hash: [u64; 8] = [
+        0x6a09e667f3bcc908,
+        0xbb67ae8584caa73b,
+        0x3c6ef372fe94f82b,
+        0xa54ff53a5f1d36f1,
+        0x510e527fade682d1,
+        0x9b05688c2b3e6c1f,
+        0x1f83d9abfb41bd6b,
+        0x5be0cd19137e2179,
+    ];
+
+    let padded = padded::<128>(message, 16);
+    for block in padded.as_chunks::<128>().0 {
+        let mut w = Secret::new([0_u64; 80]);
+        for (i, word) in block.as_chunks::<8>().0.iter().enumerate() {
+            w[i] = u64::from_be_bytes(*word);
+        }
+        for i in 16..80 {
+            let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^ (w[i - 15] >> 7);
+            let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^ (w[i - 2] >> 6);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = hash;
+        for round in 0..80 {
+            let s1 = e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add((e & f) ^ (!e & g))
+                .wrapping_add(K512[round])
+                .wrapping_add(w[round]);
+            let s0 = a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39);
+            let t2 = s0.wrapping_add((a & b) ^ (a & c) ^ (b & c));
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (hash, v) in hash.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *hash = hash.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0; 64];
+    for (bytes, word) in digest.chunks_exact_mut(8).zip(hash) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+
+    digest
+}
+
+/// The message followed by its padding: a one bit, zeros, and the length in bits
+/// in the last `len` bytes of the final `BLOCK` byte block
+fn padded<const BLOCK: usize>(message: &[u8], len: usize) -> Secret<Vec<u8>> {
+    // sized up front, growing would leave an unwiped copy of the message behind
+    let mut padded = Secret::new(Vec::with_capacity(message.len() + 2 * BLOCK));
+    padded.extend_from_slice(message);
+    padded.push(0x80);
+    while padded.len() % BLOCK != BLOCK - len {
+        padded.push(0);
+    }
+
+    let bits = (message.len() as u128 * 8).to_be_bytes();
+    padded.extend_from_slice(&bits[bits.len() - len..]);
+
+    padded
+}
+
 const fn ch(x: u32, y: u32, z: u32) -> u32 {
     (x & y) ^ (!x & z)
 }
@@ -131,6 +371,89 @@ const K: [u32; 64] = [
     0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
 ];
 
+const K512: [u64; 80] = [
+    0x428a2f98d728ae22,
+    0x7137449123ef65cd,
+    0xb5c0fbcfec4d3b2f,
+    0xe9b5dba58189dbbc,
+    0x3956c25bf348b538,
+    0x59f111f1b605d019,
+    0x923f82a4af194f9b,
+    0xab1c5ed5da6d8118,
+    0xd807aa98a3030242,
+    0x12835b0145706fbe,
+    0x243185be4ee4b28c,
+    0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f,
+    0x80deb1fe3b1696b1,
+    0x9bdc06a725c71235,
+    0xc19bf174cf692694,
+    0xe49b69c19ef14ad2,
+    0xefbe4786384f25e3,
+    0x0fc19dc68b8cd5b5,
+    0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275,
+    0x4a7484aa6ea6e483,
+    0x5cb0a9dcbd41fbd4,
+    0x76f988da831153b5,
+    0x983e5152ee66dfab,
+    0xa831c66d2db43210,
+    0xb00327c898fb213f,
+    0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2,
+    0xd5a79147930aa725,
+    0x06ca6351e003826f,
+    0x142929670a0e6e70,
+    0x27b70a8546d22ffc,
+    0x2e1b21385c26c926,
+    0x4d2c6dfc5ac42aed,
+    0x53380d139d95b3df,
+    0x650a73548baf63de,
+    0x766a0abb3c77b2a8,
+    0x81c2c92e47edaee6,
+    0x92722c851482353b,
+    0xa2bfe8a14cf10364,
+    0xa81a664bbc423001,
+    0xc24b8b70d0f89791,
+    0xc76c51a30654be30,
+    0xd192e819d6ef5218,
+    0xd69906245565a910,
+    0xf40e35855771202a,
+    0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8,
+    0x1e376c085141ab53,
+    0x2748774cdf8eeb99,
+    0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63,
+    0x4ed8aa4ae3418acb,
+    0x5b9cca4f7763e373,
+    0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc,
+    0x78a5636f43172f60,
+    0x84c87814a1f0ab72,
+    0x8cc702081a6439ec,
+    0x90befffa23631e28,
+    0xa4506cebde82bde9,
+    0xbef9a3f7b2c67915,
+    0xc67178f2e372532b,
+    0xca273eceea26619c,
+    0xd186b8c721c0c207,
+    0xeada7dd6cde0eb1e,
+    0xf57d4f7fee6ed178,
+    0x06f067aa72176fba,
+    0x0a637dc5a2c898a6,
+    0x113f9804bef90dae,
+    0x1b710b35131c471b,
+    0x28db77f523047d84,
+    0x32caab7b40c72493,
+    0x3c9ebe0a15c9bebc,
+    0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6,
+    0x597f299cfc657e2a,
+    0x5fcb6fab3ad6faec,
+    0x6c44198c4a475817,
+];
+
 #[test]
 fn test() {
     assert_eq!(
@@ -141,4 +464,283 @@ fn test() {
             0xf2, 0x00, 0x15, 0xad
         ]
     );
+
+    assert_eq!(
+        hash(b""),
+        [
+            0xe3, 0xb0, 0xc4, 0x42, 0x98, 0xfc, 0x1c, 0x14, 0x9a, 0xfb, 0xf4, 0xc8, 0x99, 0x6f,
+            0xb9, 0x24, 0x27, 0xae, 0x41, 0xe4, 0x64, 0x9b, 0x93, 0x4c, 0xa4, 0x95, 0x99, 0x1b,
+            0x78, 0x52, 0xb8, 0x55
+        ]
+    );
+
+    assert_eq!(
+        sha1(b"abc"),
+        [
+            0xa9, 0x99, 0x3e, 0x36, 0x47, 0x06, 0x81, 0x6a, 0xba, 0x3e, 0x25, 0x71, 0x78, 0x50,
+            0xc2, 0x6c, 0x9c, 0xd0, 0xd8, 0x9d
+        ]
+    );
+
+    // FIPS 180-4 examples, the second one spans two blocks
+    let two_blocks = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+    assert_eq!(
+        sha1(two_blocks),
+        [
+            0x84, 0x98, 0x3e, 0x44, 0x1c, 0x3b, 0xd2, 0x6e, 0xba, 0xae, 0x4a, 0xa1, 0xf9, 0x51,
+            0x29, 0xe5, 0xe5, 0x46, 0x70, 0xf1
+        ]
+    );
+
+    assert_eq!(
+        sha512(b"abc"),
+        [
+            0xdd, 0xaf, 0x35, 0xa1, 0x93, 0x61, 0x7a, 0xba, 0xcc, 0x41, 0x73, 0x49, 0xae, 0x20,
+            0x41, 0x31, 0x12, 0xe6, 0xfa, 0x4e, 0x89, 0xa9, 0x7e, 0xa2, 0x0a, 0x9e, 0xee, 0xe6,
+            0x4b, 0x55, 0xd3, 0x9a, 0x21, 0x92, 0x99, 0x2a, 0x27, 0x4f, 0xc1, 0xa8, 0x36, 0xba,
+            0x3c, 0x23, 0xa3, 0xfe, 0xeb, 0xbd, 0x45, 0x4d, 0x44, 0x23, 0x64, 0x3c, 0xe8, 0x0e,
+            0x2a, 0x9a, 0xc9, 0x4f, 0xa5, 0x4c, 0xa4, 0x9f
+        ]
+    );
+}
+
+#[cfg(test)]
+fn hex(text: &str) -> Vec<u8> {
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_sha256() {
+    // NIST CAVP SHA256ShortMsg.rsp and the first vector of SHA256LongMsg.rsp
+    let vectors = [
+        (
+            "",
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        ),
+        (
+            "d3",
+            "28969cdfa74a12c82f3bad960b0b000aca2ac329deea5c2328ebc6f2ba9802c1",
+        ),
+        (
+            "11af",
+            "5ca7133fa735326081558ac312c620eeca9970d1e70a4b95533d956f072d1f98",
+        ),
+        (
+            "b4190e",
+            "dff2e73091f6c05e528896c4c831b9448653dc2ff043528f6769437bc7b975c2",
+        ),
+        (
+            "74ba2521",
+            "b16aa56be3880d18cd41e68384cf1ec8c17680c45a02b1575dc1518923ae8b0e",
+        ),
+        (
+            "c299209682",
+            "f0887fe961c9cd3beab957e8222494abb969b1ce4c6557976df8b0f6d20e9166",
+        ),
+        (
+            "e1dc724d5621",
+            "eca0a060b489636225b4fa64d267dabbe44273067ac679f20820bddc6b6a90ac",
+        ),
+        (
+            "06e076f5a442d5",
+            "3fd877e27450e6bbd5d74bb82f9870c64c66e109418baa8e6bbcff355e287926",
+        ),
+        (
+            "5738c929c4f4ccb6",
+            "963bb88f27f512777aab6c8b1a02c70ec0ad651d428f870036e1917120fb48bf",
+        ),
+        (
+            "3334c58075d3f4139e",
+            "078da3d77ed43bd3037a433fd0341855023793f9afd08b4b08ea1e5597ceef20",
+        ),
+        (
+            "74cb9381d89f5aa73368",
+            "73d6fad1caaa75b43b21733561fd3958bdc555194a037c2addec19dc2d7a52bd",
+        ),
+        (
+            "76ed24a0f40a41221ebfcf",
+            "044cef802901932e46dc46b2545e6c99c0fc323a0ed99b081bda4216857f38ac",
+        ),
+        (
+            "9baf69cba317f422fe26a9a0",
+            "fe56287cd657e4afc50dba7a3a54c2a6324b886becdcd1fae473b769e551a09b",
+        ),
+        (
+            "68511cdb2dbbf3530d7fb61cbc",
+            "af53430466715e99a602fc9f5945719b04dd24267e6a98471f7a7869bd3b4313",
+        ),
+        (
+            concat!(
+                "5a86b737eaea8ee976a0a24da63e7ed7eefad18a101c1211e2b3650c5187c2a8",
+                "a650547208251f6d4237e661c7bf4c77f335390394c37fa1a9f9be836ac28509"
+            ),
+            "42e61e174fbb3897d6dd6cef3dd2802fe67b331953b06114a65c772859dfc1aa",
+        ),
+        (
+            concat!(
+                "451101250ec6f26652249d59dc974b7361d571a8101cdfd36aba3b5854d3ae08",
+                "6b5fdd4597721b66e3c0dc5d8c606d9657d0e323283a5217d1f53f2f284f57b8",
+                "5c8a61ac8924711f895c5ed90ef17745ed2d728abd22a5f7a13479a462d71b56",
+                "c19a74a40b655c58edfe0a188ad2cf46cbf30524f65d423c837dd1ff2bf462ac",
+                "4198007345bb44dbb7b1c861298cdf61982a833afc728fae1eda2f87aa2c9480",
+                "858bec"
+            ),
+            "3c593aa539fdcdae516cdf2f15000f6634185c88f505b39775fb9ab137a10aa2",
+        ),
+    ];
+
+    for (message, digest) in vectors {
+        assert_eq!(hash(&hex(message)).to_vec(), hex(digest));
+    }
+
+    // FIPS 180-4 examples, the second needs a block for the length alone
+    let digest = "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1";
+    assert_eq!(
+        hash(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq").to_vec(),
+        hex(digest)
+    );
+    let message = concat!(
+        "abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmn",
+        "hijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu"
+    );
+    let digest = "cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1";
+    assert_eq!(hash(message.as_bytes()).to_vec(), hex(digest));
+
+    // remainders around the end of the room for the length, where the padding
+    // spills into a second block
+    let digests = [
+        (
+            55,
+            "9f4390f8d30c2dd92ec9f095b65e2b9ae9b0a925a5258e241c9f1e910f734318",
+        ),
+        (
+            56,
+            "b35439a4ac6f0948b6d6f9e3c6af0f5f590ce20f1bde7090ef7970686ec6738a",
+        ),
+        (
+            57,
+            "f13b2d724659eb3bf47f2dd6af1accc87b81f09f59f2b75e5c0bed6589dfe8c6",
+        ),
+        (
+            60,
+            "11ee391211c6256460b6ed375957fadd8061cafbb31daf967db875aebd5aaad4",
+        ),
+        (
+            63,
+            "7d3e74a05d7db15bce4ad9ec0658ea98e3f06eeecf16b4c6fff2da457ddc2f34",
+        ),
+        (
+            64,
+            "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb",
+        ),
+        (
+            65,
+            "635361c48bb9eab14198e76ea8ab7f1a41685d6ad62aa9146d301d4f17eb0ae0",
+        ),
+    ];
+    for (len, digest) in digests {
+        assert_eq!(hash(&vec![b'a'; len]).to_vec(), hex(digest));
+    }
+
+    // one million times "a", fed in pieces that straddle the blocks
+    let mut sha = Sha256::new();
+    for _ in 0..1000 {
+        sha.update(&[b'a'; 1000]);
+    }
+    let digest = "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0";
+    assert_eq!(sha.finalize().to_vec(), hex(digest));
+
+    let message: Vec<u8> = (0..200).map(|i| i as u8).collect();
+    for split in 0..message.len() {
+        let mut sha = Sha256::new();
+        sha.update(&message[..split / 2]);
+        sha.update(&message[split / 2..split]);
+        sha.update(&message[split..]);
+        assert_eq!(sha.finalize(), hash(&message));
+    }
+}
+
+#[test]
+fn test_hmac() {
+    // RFC 4231 test cases 1, 2, 3, 6 and 7, the last two with keys longer than a block
+    let cases: [(Vec<u8>, Vec<u8>, &str); 5] = [
+        (
+            vec![0x0b; 20],
+            b"Hi There".to_vec(),
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+        ),
+        (
+            b"Jefe".to_vec(),
+            b"what do ya want for nothing?".to_vec(),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+        ),
+        (
+            vec![0xaa; 20],
+            vec![0xdd; 50],
+            "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+        ),
+        (
+            vec![0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+        ),
+        (
+            vec![0xaa; 131],
+            b"This is a test using a larger than block-size key and a larger than block-size data. The key needs to be hashed before being used by the HMAC algorithm.".to_vec(),
+            "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+        ),
+    ];
+
+    for (key, message, mac) in cases {
+        assert_eq!(hmac(&key, &message).to_vec(), hex(mac));
+    }
+
+    // RFC 5869 test cases 1 to 3: basic, long inputs and outputs, and no salt or info
+    let salt = hex("000102030405060708090a0b0c");
+    let info = hex("f0f1f2f3f4f5f6f7f8f9");
+    let prk = hkdf_extract(&salt, &[0x0b; 22]);
+    assert_eq!(
+        prk.to_vec(),
+        hex("077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5")
+    );
+    let mut okm = [0; 42];
+    hkdf_expand(&*prk, &info, &mut okm);
+    assert_eq!(
+        okm.to_vec(),
+        hex("3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865")
+    );
+
+    let ikm: Vec<u8> = (0x00..=0x4f).collect();
+    let salt: Vec<u8> = (0x60..=0xaf).collect();
+    let info: Vec<u8> = (0xb0..=0xff).collect();
+    let prk = hkdf_extract(&salt, &ikm);
+    assert_eq!(
+        prk.to_vec(),
+        hex("06a6b88c5853361a06104c9ceb35b45cef760014904671014a193f40c15fc244")
+    );
+    let mut okm = [0; 82];
+    hkdf_expand(&*prk, &info, &mut okm);
+    assert_eq!(
+        okm.to_vec(),
+        hex(concat!(
+            "b11e398dc80327a1c8e7f78c596a49344f012eda2d4efad8a050cc4c19afa97c",
+            "59045a99cac7827271cb41c65e590e09da3275600c2f09b8367793a9aca3db71",
+            "cc30c58179ec3e87c14c01d5c1f3434f1d87"
+        ))
+    );
+
+    let prk = hkdf_extract(&[], &[0x0b; 22]);
+    assert_eq!(
+        prk.to_vec(),
+        hex("19ef24a32c717b167f33a91d6f648bdf96596776afdb6377ac434c1c293ccb04")
+    );
+    let mut okm = [0; 42];
+    hkdf_expand(&*prk, &[], &mut okm);
+    assert_eq!(
+        okm.to_vec(),
+        hex("8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8")
+    );
 }
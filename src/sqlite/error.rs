@@ -0,0 +1,88 @@
+use std::ffi::CStr;
+
+use super::ffi;
+
+/// A failed SQLite call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    /// Primary result code, e.g. `SQLITE_CONSTRAINT`
+    pub code: u32,
+    /// Extended result code, e.g. `SQLITE_CONSTRAINT_PRIMARYKEY`
+    pub extended_code: u32,
+    pub message: String,
+    /// The SQL being prepared or run when the error occurred
+    pub sql: Option<String>,
+}
+
+impl Error {
+    /// Error for a result code that was not recorded on a connection
+    pub(super) fn from_code(code: u32, sql: Option<&str>) -> Self {
+        let message = unsafe { ffi::sqlite3_errstr(code as i32) };
+
+        Self {
+            code: code & 0xFF,
+            extended_code: code,
+            message: unsafe { to_string(message) },
+            sql: sql.map(str::to_string),
+        }
+    }
+
+    /// Error for a result code returned by a call on `db`, using its error message
+    ///
+    /// # Safety
+    ///
+    /// `db` must be a valid, open connection
+    pub(super) unsafe fn from_connection(
+        db: *mut ffi::sqlite3,
+        code: u32,
+        sql: Option<&str>,
+    ) -> Self {
+        let extended_code = unsafe { ffi::sqlite3_extended_errcode(db) } as u32;
+
+        // not every failure is recorded on the connection, e.g. misuse detected by the wrapper
+        if extended_code & 0xFF != code & 0xFF {
+            return Self::from_code(code, sql);
+        }
+
+        Self {
+            code: code & 0xFF,
+            extended_code,
+            message: unsafe { to_string(ffi::sqlite3_errmsg(db)) },
+            sql: sql.map(str::to_string),
+        }
+    }
+}
+
+impl Error {
+    /// Whether a `PRIMARY KEY` or `UNIQUE` constraint failed
+    pub fn is_unique_violation(&self) -> bool {
+        matches!(
+            self.extended_code,
+            ffi::SQLITE_CONSTRAINT_PRIMARYKEY | ffi::SQLITE_CONSTRAINT_UNIQUE
+        )
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (code {})", self.message, self.extended_code)?;
+
+        if let Some(sql) = &self.sql {
+            write!(f, " in `{sql}`")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
+unsafe fn to_string(ptr: *const std::os::raw::c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+
+    unsafe { CStr::from_ptr(ptr) }
+        .to_string_lossy()
+        .into_owned()
+}
@@ -0,0 +1,41 @@
+use super::{Connection, Error, ffi};
+
+impl Connection {
+    /// Schema version stored in the database header, 0 for a new database
+    pub fn user_version(&self) -> Result<u32, Error> {
+        let version = self.query_row("PRAGMA user_version", (), |row| row.get::<u32>(0))?;
+        Ok(version.unwrap_or(0))
+    }
+
+    /// Bring the schema up to date, `migrations[i]` upgrades from version `i` to `i + 1`
+    ///
+    /// Every migration runs in its own transaction together with the version bump,
+    /// so a failure leaves the database at the last fully applied version.
+    pub fn migrate(&self, migrations: &[&str]) -> Result<(), Error> {
+        loop {
+            let transaction = self.transaction()?;
+
+            // read inside the transaction, in case another connection migrated first
+            let version = transaction.user_version()? as usize;
+            if version == migrations.len() {
+                return transaction.commit();
+            }
+
+            let Some(sql) = migrations.get(version) else {
+                return Err(Error {
+                    code: ffi::SQLITE_ERROR,
+                    extended_code: ffi::SQLITE_ERROR,
+                    message: format!(
+                        "schema version {version} is newer than the latest known version {}",
+                        migrations.len()
+                    ),
+                    sql: None,
+                });
+            };
+
+            transaction.execute(*sql)?;
+            transaction.execute(format!("PRAGMA user_version = {}", version + 1))?;
+            transaction.commit()?;
+        }
+    }
+}
@@ -4,25 +4,43 @@ use std::{
     ptr::NonNull,
 };
 
+mod error;
 mod ffi;
+mod migrations;
+mod transaction;
+mod types;
+
+pub use error::Error;
+pub use transaction::Transaction;
+pub use types::{FromSql, Params};
 
 pub struct Connection {
     inner: NonNull<ffi::sqlite3>,
 }
 
 impl Connection {
-    pub fn open(path: &str) -> Option<Self> {
+    pub fn open(path: &str) -> Result<Self, Error> {
+        let path = CString::new(path).map_err(|_| Error::from_code(ffi::SQLITE_MISUSE, None))?;
+
         let mut inner: *mut ffi::sqlite3 = std::ptr::null_mut();
+        let result = unsafe { ffi::sqlite3_open(path.as_ptr(), &mut inner) } as u32;
+
+        let connection = Self {
+            inner: NonNull::new(inner).ok_or(Error::from_code(ffi::SQLITE_NOMEM, None))?,
+        };
+
+        if result != ffi::SQLITE_OK {
+            return Err(connection.error(result, None));
+        }
+
         unsafe {
-            ffi::sqlite3_open(CString::new(path).ok()?.as_ptr(), &mut inner);
+            ffi::sqlite3_extended_result_codes(connection.inner.as_ptr(), 1);
         }
 
-        Some(Self {
-            inner: NonNull::new(inner)?,
-        })
+        Ok(connection)
     }
 
-    pub fn prepare<'a>(&'a self, sql: &str) -> Result<Statement<'a>, u32> {
+    pub fn prepare<'a>(&'a self, sql: &str) -> Result<Statement<'a>, Error> {
         let mut statement: *mut ffi::sqlite3_stmt = std::ptr::null_mut();
         let result = unsafe {
             ffi::sqlite3_prepare_v2(
@@ -32,16 +50,30 @@ impl Connection {
                 &mut statement,
                 std::ptr::null_mut(),
             )
-        };
+        } as u32;
+
+        if result != ffi::SQLITE_OK {
+            return Err(self.error(result, Some(sql)));
+        }
+
+        // an empty string or a lone comment compiles to no statement at all
+        let inner = NonNull::new(statement).ok_or_else(|| Error {
+            code: ffi::SQLITE_MISUSE,
+            extended_code: ffi::SQLITE_MISUSE,
+            message: "no SQL statement to prepare".to_string(),
+            sql: Some(sql.to_string()),
+        })?;
 
         Ok(Statement {
-            inner: NonNull::new(statement).ok_or(result as u32)?,
+            inner,
             _conn: PhantomData,
         })
     }
 
-    pub fn execute<'a, S: Into<String>>(&'a self, sql: S) -> Result<(), u32> {
-        let cstring = CString::new(sql.into()).map_err(|_| ffi::SQLITE_ERROR)?;
+    pub fn execute<S: Into<String>>(&self, sql: S) -> Result<(), Error> {
+        let sql = sql.into();
+        let cstring = CString::new(sql.as_str())
+            .map_err(|_| Error::from_code(ffi::SQLITE_MISUSE, Some(&sql)))?;
         let result = unsafe {
             ffi::sqlite3_exec(
                 self.inner.as_ptr(),
@@ -55,21 +87,70 @@ impl Connection {
         if result == ffi::SQLITE_OK {
             Ok(())
         } else {
-            Err(result)
+            Err(self.error(result, Some(&sql)))
         }
     }
 
-    pub fn error_message(&self) -> Option<&str> {
-        unsafe {
-            let ptr = ffi::sqlite3_errmsg(self.inner.as_ptr());
-            if ptr.is_null() {
-                return None;
-            }
+    /// Prepare, bind and run a statement that returns no rows
+    pub fn execute_params<P: Params>(&self, sql: &str, params: P) -> Result<(), Error> {
+        let statement = self.prepare(sql)?;
+        params.bind(&statement)?;
+        statement.execute()
+    }
+
+    /// Run a query and map its first row, or return `None` if it has no rows
+    pub fn query_row<T, P, F>(&self, sql: &str, params: P, f: F) -> Result<Option<T>, Error>
+    where
+        P: Params,
+        F: FnOnce(&Row) -> Result<T, Error>,
+    {
+        let statement = self.prepare(sql)?;
+        params.bind(&statement)?;
+
+        match statement.step()? {
+            Step::Row(row) => f(&row).map(Some),
+            Step::Done => Ok(None),
+        }
+    }
+
+    /// Run a query and map every row
+    pub fn query_map<T, P, F>(&self, sql: &str, params: P, mut f: F) -> Result<Vec<T>, Error>
+    where
+        P: Params,
+        F: FnMut(&Row) -> Result<T, Error>,
+    {
+        let statement = self.prepare(sql)?;
+        params.bind(&statement)?;
+
+        let mut rows = Vec::new();
+        while let Step::Row(row) = statement.step()? {
+            rows.push(f(&row)?);
+        }
+
+        Ok(rows)
+    }
+
+    /// Wait up to `timeout` for another connection's lock instead of failing right away
+    /// with `SQLITE_BUSY`
+    pub fn busy_timeout(&self, timeout: std::time::Duration) -> Result<(), Error> {
+        let ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+        let result = unsafe { ffi::sqlite3_busy_timeout(self.inner.as_ptr(), ms) } as u32;
 
-            let cstr = CStr::from_ptr(ptr);
-            cstr.to_str().ok()
+        if result == ffi::SQLITE_OK {
+            Ok(())
+        } else {
+            Err(self.error(result, None))
         }
     }
+
+    /// Number of rows changed by the last `INSERT`, `UPDATE` or `DELETE`
+    pub fn changes(&self) -> u64 {
+        unsafe { ffi::sqlite3_changes64(self.inner.as_ptr()) as u64 }
+    }
+
+    fn error(&self, code: u32, sql: Option<&str>) -> Error {
+        unsafe { Error::from_connection(self.inner.as_ptr(), code, sql) }
+    }
 }
 
 impl Drop for Connection {
@@ -86,48 +167,83 @@ pub struct Statement<'conn> {
 }
 
 impl<'conn> Statement<'conn> {
-    pub fn bind_text<'a>(&'a self, parameter: u32, text: &'a str) -> Result<(), u32> {
+    pub fn bind_text(&self, parameter: u32, text: &str) -> Result<(), Error> {
+        let len = text
+            .len()
+            .try_into()
+            .map_err(|_| self.error(ffi::SQLITE_TOOBIG))?;
         let result = unsafe {
             ffi::sqlite3_bind_text(
                 self.inner.as_ptr(),
                 parameter as i32,
                 text.as_ptr() as *const i8,
-                text.len().try_into().ok().ok_or(ffi::SQLITE_RANGE)?,
-                None,
+                len,
+                transient(),
             )
         } as u32;
 
-        if result == ffi::SQLITE_OK {
-            Ok(())
-        } else {
-            Err(result)
-        }
+        self.check(result)
     }
 
-    pub fn bind_blob<'a>(&'a self, parameter: u32, blob: &'a [u8]) -> Result<(), u32> {
+    pub fn bind_blob(&self, parameter: u32, blob: &[u8]) -> Result<(), Error> {
+        let len = blob
+            .len()
+            .try_into()
+            .map_err(|_| self.error(ffi::SQLITE_TOOBIG))?;
         let result = unsafe {
-            ffi::sqlite3_bind_text(
+            ffi::sqlite3_bind_blob(
                 self.inner.as_ptr(),
                 parameter as i32,
-                blob.as_ptr() as *const i8,
-                blob.len().try_into().ok().ok_or(ffi::SQLITE_RANGE)?,
-                None,
+                blob.as_ptr() as *const std::os::raw::c_void,
+                len,
+                transient(),
             )
         } as u32;
 
-        if result == ffi::SQLITE_OK {
-            Ok(())
-        } else {
-            Err(result)
+        self.check(result)
+    }
+
+    pub fn bind_i64(&self, parameter: u32, value: i64) -> Result<(), Error> {
+        let result =
+            unsafe { ffi::sqlite3_bind_int64(self.inner.as_ptr(), parameter as i32, value) } as u32;
+
+        self.check(result)
+    }
+
+    pub fn bind_f64(&self, parameter: u32, value: f64) -> Result<(), Error> {
+        let result =
+            unsafe { ffi::sqlite3_bind_double(self.inner.as_ptr(), parameter as i32, value) }
+                as u32;
+
+        self.check(result)
+    }
+
+    pub fn bind_null(&self, parameter: u32) -> Result<(), Error> {
+        let result =
+            unsafe { ffi::sqlite3_bind_null(self.inner.as_ptr(), parameter as i32) } as u32;
+
+        self.check(result)
+    }
+
+    /// Index of a named parameter such as `:name`, `@name` or `$name`, for use with `bind_*`
+    pub fn parameter_index(&self, name: &str) -> Result<u32, Error> {
+        let cstring = CString::new(name).map_err(|_| self.error(ffi::SQLITE_MISUSE))?;
+        let index =
+            unsafe { ffi::sqlite3_bind_parameter_index(self.inner.as_ptr(), cstring.as_ptr()) };
+
+        if index == 0 {
+            return Err(self.error(ffi::SQLITE_RANGE));
         }
+
+        Ok(index as u32)
     }
 
-    pub fn execute(self) -> Result<(), u32> {
+    pub fn execute(self) -> Result<(), Error> {
         while let Step::Row(_) = self.step()? {}
         Ok(())
     }
 
-    pub fn step<'a>(&'a self) -> Result<Step<'a, 'conn>, u32> {
+    pub fn step<'a>(&'a self) -> Result<Step<'a, 'conn>, Error> {
         let result = unsafe { ffi::sqlite3_step(self.inner.as_ptr()) };
 
         match result as u32 {
@@ -135,7 +251,7 @@ impl<'conn> Statement<'conn> {
                 let columns = unsafe { ffi::sqlite3_column_count(self.inner.as_ptr()) } as u32;
 
                 if columns == 0 {
-                    return Err(ffi::SQLITE_RANGE);
+                    return Err(self.error(ffi::SQLITE_RANGE));
                 }
 
                 Ok(Step::Row(Row {
@@ -144,12 +260,35 @@ impl<'conn> Statement<'conn> {
                 }))
             }
             ffi::SQLITE_DONE => Ok(Step::Done),
-            _ => Err(result as u32),
+            _ => Err(self.error(result as u32)),
         }
     }
 
-    pub fn rows(self) -> Rows<'conn> {
-        Rows { statement: self }
+    /// The SQL this statement was prepared from
+    pub fn sql(&self) -> Option<&str> {
+        unsafe {
+            let ptr = ffi::sqlite3_sql(self.inner.as_ptr());
+            if ptr.is_null() {
+                return None;
+            }
+
+            CStr::from_ptr(ptr).to_str().ok()
+        }
+    }
+
+    fn check(&self, result: u32) -> Result<(), Error> {
+        if result == ffi::SQLITE_OK {
+            Ok(())
+        } else {
+            Err(self.error(result))
+        }
+    }
+
+    fn error(&self, code: u32) -> Error {
+        unsafe {
+            let db = ffi::sqlite3_db_handle(self.inner.as_ptr());
+            Error::from_connection(db, code, self.sql())
+        }
     }
 }
 
@@ -172,11 +311,34 @@ pub struct Row<'a, 'conn> {
 }
 
 impl Row<'_, '_> {
-    pub fn column_text<'a>(&'a self, column: u32) -> Option<&'a str> {
+    /// Read a column as any [`FromSql`] type, tuples read consecutive columns
+    pub fn get<'a, T: FromSql<'a>>(&'a self, column: u32) -> Result<T, Error> {
+        T::from_sql(self, column)
+    }
+
+    pub fn column_type(&self, column: u32) -> Option<Type> {
         if column >= self.columns {
             return None;
         }
 
+        let kind =
+            unsafe { ffi::sqlite3_column_type(self.statement.inner.as_ptr(), column as i32) };
+
+        match kind as u32 {
+            ffi::SQLITE_INTEGER => Some(Type::Integer),
+            ffi::SQLITE_FLOAT => Some(Type::Float),
+            ffi::SQLITE_TEXT => Some(Type::Text),
+            ffi::SQLITE_BLOB => Some(Type::Blob),
+            ffi::SQLITE_NULL => Some(Type::Null),
+            _ => None,
+        }
+    }
+
+    pub fn column_text(&self, column: u32) -> Option<&str> {
+        if self.column_type(column)? == Type::Null {
+            return None;
+        }
+
         unsafe {
             let ptr = ffi::sqlite3_column_text(self.statement.inner.as_ptr(), column as i32);
             let len = ffi::sqlite3_column_bytes(self.statement.inner.as_ptr(), column as i32);
@@ -190,35 +352,401 @@ impl Row<'_, '_> {
         }
     }
 
-    pub fn column_blob<'a>(&'a self, column: u32) -> Option<&'a [u8]> {
-        if column >= self.columns {
+    pub fn column_blob(&self, column: u32) -> Option<&[u8]> {
+        if self.column_type(column)? == Type::Null {
             return None;
         }
 
         unsafe {
-            let ptr = ffi::sqlite3_column_text(self.statement.inner.as_ptr(), column as i32);
+            let ptr = ffi::sqlite3_column_blob(self.statement.inner.as_ptr(), column as i32);
             let len = ffi::sqlite3_column_bytes(self.statement.inner.as_ptr(), column as i32);
 
+            // zero length blobs are returned as a null pointer
             if ptr.is_null() {
-                return None;
+                return Some(&[]);
             }
 
-            Some(std::slice::from_raw_parts(ptr, len as usize))
+            Some(std::slice::from_raw_parts(ptr as *const u8, len as usize))
         }
     }
-}
 
-pub struct Rows<'conn> {
-    statement: Statement<'conn>,
+    pub fn column_i64(&self, column: u32) -> Option<i64> {
+        if self.column_type(column)? == Type::Null {
+            return None;
+        }
+
+        Some(unsafe { ffi::sqlite3_column_int64(self.statement.inner.as_ptr(), column as i32) })
+    }
+
+    pub fn column_f64(&self, column: u32) -> Option<f64> {
+        if self.column_type(column)? == Type::Null {
+            return None;
+        }
+
+        Some(unsafe { ffi::sqlite3_column_double(self.statement.inner.as_ptr(), column as i32) })
+    }
 }
 
-impl<'a, 'conn> Iterator for &'a Rows<'conn> {
-    type Item = Row<'a, 'conn>;
+impl Row<'_, '_> {
+    fn mismatch(&self, column: u32, expected: &str) -> Error {
+        let found = match self.column_type(column) {
+            Some(kind) => format!("{kind:?}").to_uppercase(),
+            None => "missing".to_string(),
+        };
 
-    fn next(&mut self) -> Option<Self::Item> {
-        match self.statement.step() {
-            Ok(Step::Row(row)) => Some(row),
-            _ => None,
+        Error {
+            code: ffi::SQLITE_MISMATCH,
+            extended_code: ffi::SQLITE_MISMATCH,
+            message: format!("column {column} is {found}, expected {expected}"),
+            sql: self.statement.sql().map(str::to_string),
         }
     }
 }
+
+/// Storage class of a column value
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Type {
+    Integer,
+    Float,
+    Text,
+    Blob,
+    Null,
+}
+
+/// Makes SQLite copy bound text and blobs, so they don't have to outlive the statement
+fn transient() -> ffi::sqlite3_destructor_type {
+    Some(unsafe {
+        std::mem::transmute::<isize, unsafe extern "C" fn(*mut std::os::raw::c_void)>(-1)
+    })
+}
+
+#[test]
+fn test() {
+    let connection = Connection::open(":memory:").unwrap();
+    connection
+        .execute("CREATE TABLE entries (name STRING PRIMARY KEY)")
+        .unwrap();
+
+    let Err(error) = connection.prepare("SELECT missing FROM entries") else {
+        panic!("prepared a statement with an unknown column");
+    };
+    assert_eq!(error.code, ffi::SQLITE_ERROR);
+    assert_eq!(error.message, "no such column: missing");
+    assert_eq!(error.sql.as_deref(), Some("SELECT missing FROM entries"));
+
+    connection
+        .execute("INSERT INTO entries (name) VALUES ('mail')")
+        .unwrap();
+    let error = connection
+        .execute("INSERT INTO entries (name) VALUES ('mail')")
+        .unwrap_err();
+    assert_eq!(error.code, ffi::SQLITE_CONSTRAINT);
+    assert_eq!(error.extended_code, ffi::SQLITE_CONSTRAINT_PRIMARYKEY);
+    assert!(error.is_unique_violation());
+    assert!(
+        error
+            .to_string()
+            .contains("UNIQUE constraint failed: entries.name")
+    );
+
+    connection
+        .execute("UPDATE entries SET name = 'bank' WHERE name = 'mail'")
+        .unwrap();
+    assert_eq!(connection.changes(), 1);
+    connection
+        .execute("DELETE FROM entries WHERE name = 'mail'")
+        .unwrap();
+    assert_eq!(connection.changes(), 0);
+    connection
+        .execute("UPDATE entries SET name = 'mail'")
+        .unwrap();
+
+    let statement = connection
+        .prepare("INSERT INTO entries (name) VALUES (?)")
+        .unwrap();
+    let error = statement.bind_text(2, "mail").unwrap_err();
+    assert_eq!(error.code, ffi::SQLITE_RANGE);
+    assert_eq!(
+        error.sql.as_deref(),
+        Some("INSERT INTO entries (name) VALUES (?)")
+    );
+
+    statement.bind_text(1, "mail").unwrap();
+    let error = statement.execute().unwrap_err();
+    assert_eq!(error.extended_code, ffi::SQLITE_CONSTRAINT_PRIMARYKEY);
+
+    assert_eq!(
+        connection.prepare("").err().unwrap().code,
+        ffi::SQLITE_MISUSE
+    );
+}
+
+#[test]
+fn test_types() {
+    let connection = Connection::open(":memory:").unwrap();
+    connection
+        .execute("CREATE TABLE entries (name STRING, cyphertext BLOB, count INTEGER, ratio REAL, missing BLOB)")
+        .unwrap();
+
+    let blob = [0xFF, 0x00, 0xC3, 0x28, 0x00];
+    let statement = connection
+        .prepare("INSERT INTO entries VALUES (:name, :cyphertext, :count, :ratio, :missing)")
+        .unwrap();
+    statement
+        .bind_text(statement.parameter_index(":name").unwrap(), "mail")
+        .unwrap();
+    statement
+        .bind_blob(statement.parameter_index(":cyphertext").unwrap(), &blob)
+        .unwrap();
+    statement
+        .bind_i64(statement.parameter_index(":count").unwrap(), i64::MIN)
+        .unwrap();
+    statement
+        .bind_f64(statement.parameter_index(":ratio").unwrap(), 0.5)
+        .unwrap();
+    statement
+        .bind_null(statement.parameter_index(":missing").unwrap())
+        .unwrap();
+    assert!(statement.parameter_index(":unknown").is_err());
+    statement.execute().unwrap();
+
+    let statement = connection
+        .prepare("SELECT name, cyphertext, count, ratio, missing, typeof(cyphertext) FROM entries")
+        .unwrap();
+    let Step::Row(row) = statement.step().unwrap() else {
+        panic!("inserted row is missing");
+    };
+
+    assert_eq!(row.column_type(0), Some(Type::Text));
+    assert_eq!(row.column_type(1), Some(Type::Blob));
+    assert_eq!(row.column_type(2), Some(Type::Integer));
+    assert_eq!(row.column_type(3), Some(Type::Float));
+    assert_eq!(row.column_type(4), Some(Type::Null));
+    assert_eq!(row.column_type(6), None);
+
+    assert_eq!(row.column_text(0), Some("mail"));
+    assert_eq!(row.column_blob(1), Some(blob.as_slice()));
+    assert_eq!(row.column_i64(2), Some(i64::MIN));
+    assert_eq!(row.column_f64(3), Some(0.5));
+    assert_eq!(row.column_blob(4), None);
+    assert_eq!(row.column_i64(4), None);
+    assert_eq!(row.column_text(5), Some("blob"));
+
+    // bound values are copied, so the buffer may be dropped before the statement runs
+    let statement = connection
+        .prepare("INSERT INTO entries (cyphertext) VALUES (?)")
+        .unwrap();
+    let buffer = blob.to_vec();
+    statement.bind_blob(1, &buffer).unwrap();
+    drop(buffer);
+    statement.bind_blob(1, &[]).unwrap();
+    statement.execute().unwrap();
+
+    let statement = connection
+        .prepare("SELECT cyphertext FROM entries WHERE name IS NULL")
+        .unwrap();
+    let Step::Row(row) = statement.step().unwrap() else {
+        panic!("inserted row is missing");
+    };
+    assert_eq!(row.column_blob(0), Some([].as_slice()));
+}
+
+#[test]
+fn test_typed() {
+    let connection = Connection::open(":memory:").unwrap();
+    connection
+        .execute("CREATE TABLE entries (name STRING PRIMARY KEY, account STRING, cyphertext BLOB, uses INTEGER)")
+        .unwrap();
+
+    connection
+        .execute_params(
+            "INSERT INTO entries VALUES (?, ?, ?, ?)",
+            (
+                "mail",
+                "me@example.com".to_string(),
+                vec![1_u8, 2, 3],
+                7_u32,
+            ),
+        )
+        .unwrap();
+    connection
+        .execute_params(
+            "INSERT INTO entries VALUES (?, ?, ?, ?)",
+            ("bank", None::<&str>, [4_u8, 5], None::<i64>),
+        )
+        .unwrap();
+
+    let entries = connection
+        .query_map(
+            "SELECT name, account, cyphertext, uses FROM entries ORDER BY name",
+            (),
+            |row| row.get::<(String, Option<String>, Vec<u8>, Option<u32>)>(0),
+        )
+        .unwrap();
+    assert_eq!(
+        entries,
+        [
+            ("bank".to_string(), None, vec![4, 5], None),
+            (
+                "mail".to_string(),
+                Some("me@example.com".to_string()),
+                vec![1, 2, 3],
+                Some(7)
+            ),
+        ]
+    );
+
+    let uses = connection
+        .query_row(
+            "SELECT uses FROM entries WHERE name = ?",
+            ("mail",),
+            |row| row.get::<i64>(0),
+        )
+        .unwrap();
+    assert_eq!(uses, Some(7));
+
+    let missing = connection
+        .query_row(
+            "SELECT uses FROM entries WHERE name = ?",
+            ("shop",),
+            |row| row.get::<i64>(0),
+        )
+        .unwrap();
+    assert_eq!(missing, None);
+
+    // borrowed values live as long as the row
+    let length = connection
+        .query_row(
+            "SELECT name, cyphertext FROM entries WHERE uses = 7",
+            (),
+            |row| {
+                let (name, cyphertext) = row.get::<(&str, &[u8])>(0)?;
+                Ok(name.len() + cyphertext.len())
+            },
+        )
+        .unwrap();
+    assert_eq!(length, Some(7));
+
+    let error = connection
+        .query_row("SELECT name FROM entries", (), |row| row.get::<i64>(0))
+        .unwrap_err();
+    assert_eq!(error.code, ffi::SQLITE_MISMATCH);
+
+    let error = connection
+        .query_row(
+            "SELECT account FROM entries WHERE name = 'bank'",
+            (),
+            |row| row.get::<String>(0),
+        )
+        .unwrap_err();
+    assert_eq!(error.code, ffi::SQLITE_MISMATCH);
+
+    let params: [&dyn types::ToSql; 2] = [&"shop", &300_u16];
+    connection
+        .execute_params(
+            "INSERT INTO entries (name, uses) VALUES (?, ?)",
+            &params[..],
+        )
+        .unwrap();
+    let error = connection
+        .query_row("SELECT uses FROM entries WHERE name = 'shop'", (), |row| {
+            row.get::<u8>(0)
+        })
+        .unwrap_err();
+    assert_eq!(error.code, ffi::SQLITE_MISMATCH);
+}
+
+#[test]
+fn test_transaction() {
+    let connection = Connection::open(":memory:").unwrap();
+    connection
+        .execute("CREATE TABLE entries (name STRING PRIMARY KEY)")
+        .unwrap();
+
+    let count = |connection: &Connection| {
+        connection
+            .query_row("SELECT COUNT(*) FROM entries", (), |row| row.get::<i64>(0))
+            .unwrap()
+            .unwrap()
+    };
+
+    // dropped without committing
+    {
+        let transaction = connection.transaction().unwrap();
+        transaction
+            .execute("INSERT INTO entries (name) VALUES ('mail')")
+            .unwrap();
+        assert_eq!(count(&transaction), 1);
+    }
+    assert_eq!(count(&connection), 0);
+
+    // a failed nested savepoint only undoes its own changes
+    let transaction = connection.transaction().unwrap();
+    transaction
+        .execute("INSERT INTO entries (name) VALUES ('mail')")
+        .unwrap();
+    {
+        let nested = transaction.transaction().unwrap();
+        nested
+            .execute("INSERT INTO entries (name) VALUES ('bank')")
+            .unwrap();
+        assert!(
+            nested
+                .execute("INSERT INTO entries (name) VALUES ('mail')")
+                .is_err()
+        );
+    }
+    assert_eq!(count(&transaction), 1);
+
+    let nested = transaction.transaction().unwrap();
+    nested
+        .execute("INSERT INTO entries (name) VALUES ('shop')")
+        .unwrap();
+    nested.commit().unwrap();
+    transaction.commit().unwrap();
+    assert_eq!(count(&connection), 2);
+
+    // a second connection can't start a write while one is open
+    let path = crate::TempPath::new();
+    let path = path.to_str().unwrap();
+    let first = Connection::open(path).unwrap();
+    let second = Connection::open(path).unwrap();
+    let transaction = first.transaction().unwrap();
+    let Err(error) = second.transaction() else {
+        panic!("two write transactions were open at once");
+    };
+    assert_eq!(error.code, ffi::SQLITE_BUSY);
+
+    // or only after waiting for it
+    let timeout = std::time::Duration::from_millis(50);
+    second.busy_timeout(timeout).unwrap();
+    let busy_timeout = second
+        .query_row("PRAGMA busy_timeout", (), |row| row.get::<i64>(0))
+        .unwrap();
+    assert_eq!(busy_timeout, Some(50));
+    let start = std::time::Instant::now();
+    assert!(second.transaction().is_err());
+    assert!(start.elapsed() >= timeout);
+    transaction.commit().unwrap();
+    second.transaction().unwrap().commit().unwrap();
+    drop((first, second));
+
+    // a commit that fails is rolled back instead of leaving the transaction open
+    connection.execute("PRAGMA foreign_keys = ON").unwrap();
+    connection
+        .execute(
+            "CREATE TABLE fields (entry STRING REFERENCES entries (name) DEFERRABLE INITIALLY DEFERRED)",
+        )
+        .unwrap();
+    let transaction = connection.transaction().unwrap();
+    transaction
+        .execute("INSERT INTO fields (entry) VALUES ('missing')")
+        .unwrap();
+    let error = transaction.commit().unwrap_err();
+    assert_eq!(error.code, ffi::SQLITE_CONSTRAINT);
+    assert!(!connection.in_transaction());
+    let fields = connection
+        .query_row("SELECT COUNT(*) FROM fields", (), |row| row.get::<i64>(0))
+        .unwrap();
+    assert_eq!(fields, Some(0));
+}
@@ -0,0 +1,80 @@
+use std::ops::Deref;
+
+use super::{Connection, Error, ffi};
+
+/// Name used for nested savepoints, SQLite resolves it to the innermost one
+const SAVEPOINT: &str = "goonpass_savepoint";
+
+/// A transaction, or a savepoint when nested inside another one, that is
+/// rolled back when dropped unless [`Transaction::commit`] is called
+pub struct Transaction<'conn> {
+    connection: &'conn Connection,
+    nested: bool,
+    finished: bool,
+}
+
+impl Connection {
+    /// Begin a write transaction, or a savepoint if a transaction is already open
+    pub fn transaction(&self) -> Result<Transaction<'_>, Error> {
+        let nested = self.in_transaction();
+
+        if nested {
+            self.execute(format!("SAVEPOINT {SAVEPOINT}"))?;
+        } else {
+            self.execute("BEGIN IMMEDIATE")?;
+        }
+
+        Ok(Transaction {
+            connection: self,
+            nested,
+            finished: false,
+        })
+    }
+
+    /// Whether a transaction is open, rather than each statement committing on its own
+    pub fn in_transaction(&self) -> bool {
+        unsafe { ffi::sqlite3_get_autocommit(self.inner.as_ptr()) == 0 }
+    }
+}
+
+impl Transaction<'_> {
+    pub fn commit(mut self) -> Result<(), Error> {
+        if self.nested {
+            self.connection.execute(format!("RELEASE {SAVEPOINT}"))?;
+        } else {
+            self.connection.execute("COMMIT")?;
+        }
+
+        // a failed commit leaves the transaction open, dropping `self` rolls it back
+        self.finished = true;
+
+        Ok(())
+    }
+}
+
+impl Deref for Transaction<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.connection
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        // errors can't be reported from drop, and SQLite rolls back on close anyway
+        if self.nested {
+            // rolling back to a savepoint leaves it open, so it has to be released too
+            self.connection
+                .execute(format!("ROLLBACK TO {SAVEPOINT}"))
+                .ok();
+            self.connection.execute(format!("RELEASE {SAVEPOINT}")).ok();
+        } else {
+            self.connection.execute("ROLLBACK").ok();
+        }
+    }
+}
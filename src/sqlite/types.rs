@@ -0,0 +1,208 @@
+use super::{Error, Row, Statement, Type, ffi};
+
+/// A value that can be bound to a statement parameter
+pub trait ToSql {
+    fn to_sql(&self, statement: &Statement, parameter: u32) -> Result<(), Error>;
+}
+
+/// A value that can be read from a result column, possibly borrowing from the row
+pub trait FromSql<'a>: Sized {
+    fn from_sql(row: &'a Row<'_, '_>, column: u32) -> Result<Self, Error>;
+}
+
+/// A set of positional parameters, bound starting at `?1`
+pub trait Params {
+    fn bind(&self, statement: &Statement) -> Result<(), Error>;
+}
+
+impl<T: ToSql + ?Sized> ToSql for &T {
+    fn to_sql(&self, statement: &Statement, parameter: u32) -> Result<(), Error> {
+        (**self).to_sql(statement, parameter)
+    }
+}
+
+impl ToSql for str {
+    fn to_sql(&self, statement: &Statement, parameter: u32) -> Result<(), Error> {
+        statement.bind_text(parameter, self)
+    }
+}
+
+impl ToSql for String {
+    fn to_sql(&self, statement: &Statement, parameter: u32) -> Result<(), Error> {
+        statement.bind_text(parameter, self)
+    }
+}
+
+impl ToSql for [u8] {
+    fn to_sql(&self, statement: &Statement, parameter: u32) -> Result<(), Error> {
+        statement.bind_blob(parameter, self)
+    }
+}
+
+impl<const N: usize> ToSql for [u8; N] {
+    fn to_sql(&self, statement: &Statement, parameter: u32) -> Result<(), Error> {
+        statement.bind_blob(parameter, self)
+    }
+}
+
+impl ToSql for Vec<u8> {
+    fn to_sql(&self, statement: &Statement, parameter: u32) -> Result<(), Error> {
+        statement.bind_blob(parameter, self)
+    }
+}
+
+impl ToSql for f64 {
+    fn to_sql(&self, statement: &Statement, parameter: u32) -> Result<(), Error> {
+        statement.bind_f64(parameter, *self)
+    }
+}
+
+impl ToSql for bool {
+    fn to_sql(&self, statement: &Statement, parameter: u32) -> Result<(), Error> {
+        statement.bind_i64(parameter, *self as i64)
+    }
+}
+
+impl<T: ToSql> ToSql for Option<T> {
+    fn to_sql(&self, statement: &Statement, parameter: u32) -> Result<(), Error> {
+        match self {
+            Some(value) => value.to_sql(statement, parameter),
+            None => statement.bind_null(parameter),
+        }
+    }
+}
+
+impl<'a> FromSql<'a> for &'a str {
+    fn from_sql(row: &'a Row<'_, '_>, column: u32) -> Result<Self, Error> {
+        expect(row, column, &[Type::Text])?;
+        row.column_text(column)
+            .ok_or_else(|| row.mismatch(column, "valid UTF-8"))
+    }
+}
+
+impl FromSql<'_> for String {
+    fn from_sql(row: &Row<'_, '_>, column: u32) -> Result<Self, Error> {
+        <&str>::from_sql(row, column).map(str::to_string)
+    }
+}
+
+impl<'a> FromSql<'a> for &'a [u8] {
+    fn from_sql(row: &'a Row<'_, '_>, column: u32) -> Result<Self, Error> {
+        // blobs written by older versions were bound as text
+        expect(row, column, &[Type::Blob, Type::Text])?;
+        row.column_blob(column)
+            .ok_or_else(|| row.mismatch(column, "BLOB"))
+    }
+}
+
+impl FromSql<'_> for Vec<u8> {
+    fn from_sql(row: &Row<'_, '_>, column: u32) -> Result<Self, Error> {
+        <&[u8]>::from_sql(row, column).map(<[u8]>::to_vec)
+    }
+}
+
+impl FromSql<'_> for f64 {
+    fn from_sql(row: &Row<'_, '_>, column: u32) -> Result<Self, Error> {
+        expect(row, column, &[Type::Float, Type::Integer])?;
+        row.column_f64(column)
+            .ok_or_else(|| row.mismatch(column, "REAL"))
+    }
+}
+
+impl FromSql<'_> for bool {
+    fn from_sql(row: &Row<'_, '_>, column: u32) -> Result<Self, Error> {
+        i64::from_sql(row, column).map(|value| value != 0)
+    }
+}
+
+impl<'a, T: FromSql<'a>> FromSql<'a> for Option<T> {
+    fn from_sql(row: &'a Row<'_, '_>, column: u32) -> Result<Self, Error> {
+        match row.column_type(column) {
+            Some(Type::Null) => Ok(None),
+            _ => T::from_sql(row, column).map(Some),
+        }
+    }
+}
+
+macro_rules! integer {
+    ($($int:ty),+) => {
+        $(
+            impl ToSql for $int {
+                fn to_sql(&self, statement: &Statement, parameter: u32) -> Result<(), Error> {
+                    let value = i64::try_from(*self)
+                        .map_err(|_| statement.error(ffi::SQLITE_TOOBIG))?;
+                    statement.bind_i64(parameter, value)
+                }
+            }
+
+            impl FromSql<'_> for $int {
+                fn from_sql(row: &Row<'_, '_>, column: u32) -> Result<Self, Error> {
+                    expect(row, column, &[Type::Integer])?;
+                    row.column_i64(column)
+                        .and_then(|value| <$int>::try_from(value).ok())
+                        .ok_or_else(|| row.mismatch(column, stringify!($int)))
+                }
+            }
+        )+
+    };
+}
+
+integer!(i8, i16, i32, i64, u8, u16, u32, u64, usize);
+
+macro_rules! tuple {
+    ($($name:ident $index:tt),+) => {
+        impl<$($name: ToSql),+> Params for ($($name,)+) {
+            fn bind(&self, statement: &Statement) -> Result<(), Error> {
+                $(self.$index.to_sql(statement, $index + 1)?;)+
+                Ok(())
+            }
+        }
+
+        /// Reads consecutive columns, starting at `column`
+        impl<'a, $($name: FromSql<'a>),+> FromSql<'a> for ($($name,)+) {
+            fn from_sql(row: &'a Row<'_, '_>, column: u32) -> Result<Self, Error> {
+                Ok(($($name::from_sql(row, column + $index)?,)+))
+            }
+        }
+    };
+}
+
+tuple!(A 0);
+tuple!(A 0, B 1);
+tuple!(A 0, B 1, C 2);
+tuple!(A 0, B 1, C 2, D 3);
+tuple!(A 0, B 1, C 2, D 3, E 4);
+tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+
+impl Params for () {
+    fn bind(&self, _: &Statement) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<T: ToSql> Params for [T] {
+    fn bind(&self, statement: &Statement) -> Result<(), Error> {
+        for (i, value) in self.iter().enumerate() {
+            value.to_sql(statement, i as u32 + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<P: Params + ?Sized> Params for &P {
+    fn bind(&self, statement: &Statement) -> Result<(), Error> {
+        (**self).bind(statement)
+    }
+}
+
+fn expect(row: &Row, column: u32, types: &[Type]) -> Result<(), Error> {
+    match row.column_type(column) {
+        Some(kind) if types.contains(&kind) => Ok(()),
+        Some(_) => Err(row.mismatch(column, &format!("{:?}", types[0]).to_uppercase())),
+        None => Err(row.statement.error(ffi::SQLITE_RANGE)),
+    }
+}
@@ -1,19 +1,285 @@
-use crate::State;
+use crate::{
+    ChangeMaster, EditField, FieldKind, Login, Password, State, algo,
+    error::Error,
+    generator, keyfile, rekey,
+    secret::{Secret, TEXT_LIMIT},
+};
 use eframe::egui;
+use std::path::{Path, PathBuf};
 
 impl State {
-    pub fn ui(&mut self, ui: &mut egui::Ui) {
+    /// Returns whether the vault should be locked
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        if self.rekey.is_some() {
+            self.rekey(ui);
+            self.show_toast(ui.ctx());
+            return false;
+        }
+
+        let mut lock = false;
+
         ui.vertical_centered(|ui| {
             ui.heading("GoonPass");
+            if let Some(name) = self.vault.file_name() {
+                ui.weak(name.to_string_lossy())
+                    .on_hover_text(self.vault.display().to_string());
+            }
+            lock = ui
+                .add_enabled(self.recovery_code.is_none(), egui::Button::new("Lock"))
+                .on_hover_text("Lock the vault, or switch to another one")
+                .on_disabled_hover_text("Write down the recovery code first")
+                .clicked();
             ui.separator();
 
+            if self.recovery_code.is_some() {
+                self.recovery_code(ui);
+                return;
+            }
+
+            if self.recovered {
+                self.recovered(ui);
+                return;
+            }
+
             self.new_password(ui);
 
             if !self.passwords.is_empty() {
                 ui.separator();
                 self.saved_passwords(ui);
             }
+
+            ui.separator();
+            egui::CollapsingHeader::new("Settings").show(ui, |ui| self.settings(ui));
         });
+
+        self.show_toast(ui.ctx());
+        self.expire_clipboard(ui.ctx());
+
+        // locking drops the state, and with it the only copy of the recovery code
+        if self.recovery_code.is_some() {
+            self.last_activity = std::time::Instant::now();
+            return false;
+        }
+
+        lock || self.idle(ui.ctx())
+    }
+
+    /// Show a new recovery code until the user confirms they have written it down
+    fn recovery_code(&mut self, ui: &mut egui::Ui) {
+        let Some(code) = &self.recovery_code else {
+            return;
+        };
+
+        ui.label("Recovery code");
+        ui.label(
+            "Write this code down and keep it somewhere safe. \
+             It unlocks the vault if you forget the master password, and won't be shown again.",
+        );
+        ui.label(egui::RichText::new(code.as_str()).monospace().strong());
+
+        if ui.button("I have written it down").clicked() {
+            self.recovery_code = None;
+        }
+    }
+
+    /// Ask for a new master password after unlocking with the recovery code
+    fn recovered(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            "Unlocked with the recovery code, choose a new master password. \
+             The vault won't need a key file any more.",
+        );
+
+        let valid = new_master(&mut self.change_master, ui);
+        if ui.add_enabled(valid, egui::Button::new("Save")).clicked()
+            && let Err(error) = self.set_master()
+        {
+            self.toast(error, ui.ctx());
+        }
+    }
+
+    /// Progress of changing the master password, the vault can't be used meanwhile
+    fn rekey(&mut self, ui: &mut egui::Ui) {
+        let Some(rekey) = &mut self.rekey else {
+            return;
+        };
+
+        let result = rekey.poll();
+
+        ui.vertical_centered(|ui| {
+            ui.heading("GoonPass");
+            ui.separator();
+
+            match rekey.progress {
+                rekey::Progress::Verifying => {
+                    ui.label("Checking the current master password...");
+                    ui.spinner();
+                }
+                rekey::Progress::Deriving => {
+                    ui.label("Deriving the new key...");
+                    ui.spinner();
+                }
+            }
+        });
+
+        let Some(result) = result else {
+            return;
+        };
+
+        self.rekey = None;
+        if let Err(error) = result {
+            self.toast(error, ui.ctx());
+        }
+    }
+
+    /// Whether the window was minimised or left idle for longer than `lock_after`
+    fn idle(&mut self, ctx: &egui::Context) -> bool {
+        let (active, minimised) = ctx.input(|input| {
+            (
+                !input.events.is_empty(),
+                input.viewport().minimized.unwrap_or(false),
+            )
+        });
+
+        if minimised {
+            return true;
+        }
+
+        if active {
+            self.last_activity = std::time::Instant::now();
+        }
+
+        if self.lock_after == 0 {
+            return false;
+        }
+
+        let timeout = std::time::Duration::from_secs(u64::from(self.lock_after) * 60);
+        let idle = self.last_activity.elapsed();
+        if idle >= timeout {
+            return true;
+        }
+
+        // wake up to lock even if there is no input
+        ctx.request_repaint_after(timeout - idle);
+        false
+    }
+
+    /// Clear the clipboard once the copied password expires
+    fn expire_clipboard(&mut self, ctx: &egui::Context) {
+        let Some(copied) = &self.copied else {
+            return;
+        };
+
+        let remaining = copied.expires - ctx.input(|input| input.time);
+        if remaining > 0.0 {
+            // repaint every second for the countdown
+            ctx.request_repaint_after(std::time::Duration::from_secs_f64(remaining.min(1.0)));
+            return;
+        }
+
+        if let Err(error) = crate::clear_clipboard(&self.clipboard_key, &copied.mac) {
+            self.toast(error, ctx);
+        }
+        self.copied = None;
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        let mut minutes = self.lock_after;
+        ui.horizontal(|ui| {
+            ui.label("Lock after");
+            let response = ui.add(
+                egui::DragValue::new(&mut minutes)
+                    .range(0..=120)
+                    .suffix(" min"),
+            );
+            ui.label("idle (0 = never)");
+
+            if response.changed()
+                && let Err(error) = self.set_lock_after(minutes)
+            {
+                self.toast(error, ui.ctx());
+            }
+        });
+
+        let mut seconds = self.clear_clipboard_after;
+        ui.horizontal(|ui| {
+            ui.label("Clear clipboard after");
+            let response = ui.add(
+                egui::DragValue::new(&mut seconds)
+                    .range(0..=600)
+                    .suffix(" s"),
+            );
+            ui.label("(0 = never)");
+
+            if response.changed()
+                && let Err(error) = self.set_clear_clipboard_after(seconds)
+            {
+                self.toast(error, ui.ctx());
+            }
+        });
+
+        let mut encrypt = self.encrypt_metadata;
+        let response = ui
+            .checkbox(&mut encrypt, "Encrypt names and accounts")
+            .on_hover_text("Hide which sites and accounts the vault file holds");
+        if response.changed()
+            && let Err(error) = self.set_encrypt_metadata(encrypt)
+        {
+            self.toast(error, ui.ctx());
+        }
+
+        ui.separator();
+        ui.label("Change master password");
+
+        let form = &mut self.change_master;
+        egui::TextEdit::singleline(&mut *form.current_field)
+            .char_limit(TEXT_LIMIT)
+            .password(true)
+            .hint_text("Current Master Password...")
+            .desired_width(f32::INFINITY)
+            .margin(8)
+            .show(ui);
+
+        let valid = new_master(form, ui) && !form.current_field.is_empty();
+        if ui.add_enabled(valid, egui::Button::new("Change")).clicked() {
+            self.change_master(ui.ctx());
+        }
+
+        ui.separator();
+        if ui.button("New recovery code").clicked()
+            && let Err(error) = self.new_recovery_code()
+        {
+            self.toast(error, ui.ctx());
+        }
+    }
+
+    /// Show `error` at the bottom of the window for a few seconds
+    pub fn toast(&mut self, error: Error, ctx: &egui::Context) {
+        let expires = ctx.input(|input| input.time) + 5.0;
+        self.toast = Some((error.to_string(), expires));
+    }
+
+    fn show_toast(&mut self, ctx: &egui::Context) {
+        let Some((message, expires)) = &self.toast else {
+            return;
+        };
+
+        let remaining = expires - ctx.input(|input| input.time);
+        if remaining <= 0.0 {
+            self.toast = None;
+            return;
+        }
+
+        egui::Area::new(egui::Id::new("toast"))
+            .anchor(egui::Align2::CENTER_BOTTOM, egui::Vec2::new(0.0, -8.0))
+            .show(ctx, |ui| {
+                egui::Frame::new()
+                    .fill(egui::Color32::from_rgb(96, 24, 24))
+                    .corner_radius(12)
+                    .inner_margin(8)
+                    .show(ui, |ui| ui.label(message));
+            });
+
+        ctx.request_repaint_after(std::time::Duration::from_secs_f64(remaining));
     }
 
     fn new_password(&mut self, ui: &mut egui::Ui) {
@@ -36,6 +302,12 @@ impl State {
                     .margin(8)
                     .show(ui);
 
+                egui::TextEdit::singleline(&mut self.url_field)
+                    .hint_text("Website (optional)...")
+                    .desired_width(f32::INFINITY)
+                    .margin(8)
+                    .show(ui);
+
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
                     ui.set_width(ui.available_width());
 
@@ -45,20 +317,76 @@ impl State {
                         .corner_radius(15)
                         .fill(egui::Color32::from_gray(10))
                         .sense(egui::Sense::CLICK);
-                    if ui.add(button).clicked() {
-                        self.add_password();
+                    if ui.add(button).clicked()
+                        && let Err(error) = self.add_password()
+                    {
+                        self.toast(error, ui.ctx());
+                    }
+
+                    let icon = egui::Image::new(egui::include_image!("../assets/dice.svg"))
+                        .fit_to_exact_size(egui::Vec2::new(14.0, 14.0));
+                    let button = egui::Button::new(icon)
+                        .corner_radius(15)
+                        .fill(egui::Color32::from_gray(10))
+                        .sense(egui::Sense::CLICK);
+                    if ui.add(button).on_hover_text("Generate").clicked()
+                        && let Some(password) = self.generator.generate()
+                    {
+                        self.plaintext_field = password;
                     }
 
-                    egui::TextEdit::singleline(&mut self.plaintext_field)
+                    egui::TextEdit::singleline(&mut *self.plaintext_field)
+                        .char_limit(TEXT_LIMIT)
                         .password(true)
                         .hint_text("Enter Password...")
                         .desired_width(f32::INFINITY)
                         .margin(8)
                         .show(ui);
                 });
+
+                egui::CollapsingHeader::new("Generator").show(ui, |ui| self.generator(ui));
             });
     }
 
+    fn generator(&mut self, ui: &mut egui::Ui) {
+        let generator = &mut self.generator;
+
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut generator.passphrase, false, "Password");
+            ui.selectable_value(&mut generator.passphrase, true, "Passphrase");
+        });
+
+        if generator.passphrase {
+            ui.add(egui::Slider::new(&mut generator.words, 3..=10).text("words"));
+        } else {
+            ui.add(egui::Slider::new(&mut generator.length, 8..=64).text("characters"));
+
+            for (class, minimum) in generator::Class::ALL.iter().zip(&mut generator.classes) {
+                ui.horizontal(|ui| {
+                    let mut enabled = minimum.is_some();
+                    if ui.checkbox(&mut enabled, class.label()).changed() {
+                        *minimum = enabled.then_some(1);
+                    }
+
+                    if let Some(minimum) = minimum {
+                        ui.add(
+                            egui::DragValue::new(minimum)
+                                .range(0..=10)
+                                .prefix("at least "),
+                        );
+                    }
+                });
+            }
+
+            ui.checkbox(
+                &mut generator.exclude_ambiguous,
+                "Exclude ambiguous characters",
+            );
+        }
+
+        ui.label(format!("{:.0} bits of entropy", generator.entropy()));
+    }
+
     fn saved_passwords(&mut self, ui: &mut egui::Ui) {
         egui::Frame::new()
             .fill(egui::Color32::from_gray(16))
@@ -66,8 +394,38 @@ impl State {
             .inner_margin(8)
             .show(ui, |ui| {
                 ui.set_width(ui.available_width());
+
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::Image::new(egui::include_image!("../assets/search.svg"))
+                            .fit_to_exact_size(egui::Vec2::new(14.0, 14.0)),
+                    );
+                    egui::TextEdit::singleline(&mut self.search_field)
+                        .hint_text("Search...")
+                        .desired_width(f32::INFINITY)
+                        .margin(8)
+                        .show(ui);
+                });
+
+                // iterate a copy, since the buttons below modify the list
+                let passwords = self.passwords.clone();
+                let results = algo::search(&self.search_field, &passwords);
+                if results.is_empty() {
+                    ui.label("No matches");
+                }
+
                 egui::ScrollArea::vertical().show(ui, |ui| {
-                    for (i, password) in self.passwords.clone().iter().enumerate() {
+                    for i in results {
+                        let password = &passwords[i];
+                        if self
+                            .edit
+                            .as_ref()
+                            .is_some_and(|edit| edit.original == password.name)
+                        {
+                            self.edit_password(ui);
+                            continue;
+                        }
+
                         ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
                             let icon =
                                 egui::Image::new(egui::include_image!("../assets/trash.svg"))
@@ -76,8 +434,10 @@ impl State {
                                 .corner_radius(15)
                                 .fill(egui::Color32::from_gray(10))
                                 .sense(egui::Sense::CLICK);
-                            if ui.add(button).clicked() {
-                                self.remove_password(i, &password.name);
+                            if ui.add(button).clicked()
+                                && let Err(error) = self.remove_password(i, password)
+                            {
+                                self.toast(error, ui.ctx());
                             }
 
                             let icon =
@@ -87,41 +447,500 @@ impl State {
                                 .corner_radius(15)
                                 .fill(egui::Color32::from_gray(10))
                                 .sense(egui::Sense::CLICK);
-                            if ui.add(button).clicked() {
-                                self.copy_password(password, ui.ctx());
+                            if ui.add(button).clicked()
+                                && let Err(error) = self.copy_password(password, ui.ctx())
+                            {
+                                self.toast(error, ui.ctx());
+                            }
+
+                            let icon = egui::Image::new(egui::include_image!("../assets/edit.svg"))
+                                .fit_to_exact_size(egui::Vec2::new(14.0, 14.0));
+                            let button = egui::Button::new(icon)
+                                .corner_radius(15)
+                                .fill(egui::Color32::from_gray(10))
+                                .sense(egui::Sense::CLICK);
+                            if ui.add(button).clicked()
+                                && let Err(error) = self.start_edit(password)
+                            {
+                                self.toast(error, ui.ctx());
+                            }
+
+                            if password.totp.is_some() {
+                                self.totp(password, ui);
+                            }
+
+                            if let Some(copied) = &self.copied
+                                && copied.name == password.name
+                            {
+                                let remaining = copied.expires - ui.input(|input| input.time);
+                                ui.label(format!("{:.0}s", remaining.max(0.0).ceil()))
+                                    .on_hover_text("Time until the clipboard is cleared");
                             }
 
                             ui.with_layout(egui::Layout::top_down(egui::Align::Min), |ui| {
-                                ui.label(egui::RichText::new(&password.name).strong());
+                                let name =
+                                    egui::Label::new(egui::RichText::new(&password.name).strong())
+                                        .sense(egui::Sense::click());
+                                if ui.add(name).on_hover_text("Show details").clicked()
+                                    && let Err(error) = self.toggle_detail(password)
+                                {
+                                    self.toast(error, ui.ctx());
+                                }
                                 ui.label(&password.account);
                             });
                         });
+
+                        if self
+                            .detail
+                            .as_ref()
+                            .is_some_and(|detail| detail.name == password.name)
+                        {
+                            self.detail(password, ui);
+                        }
                     }
                 });
             });
     }
+
+    fn edit_password(&mut self, ui: &mut egui::Ui) {
+        let Some(edit) = &mut self.edit else {
+            return;
+        };
+
+        egui::TextEdit::singleline(&mut edit.name_field)
+            .hint_text("Name...")
+            .desired_width(f32::INFINITY)
+            .margin(8)
+            .show(ui);
+
+        egui::TextEdit::singleline(&mut edit.account_field)
+            .hint_text("Account...")
+            .desired_width(f32::INFINITY)
+            .margin(8)
+            .show(ui);
+
+        egui::TextEdit::singleline(&mut *edit.plaintext_field)
+            .char_limit(TEXT_LIMIT)
+            .password(true)
+            .hint_text("Enter Password...")
+            .desired_width(f32::INFINITY)
+            .margin(8)
+            .show(ui);
+
+        egui::TextEdit::singleline(&mut edit.url_field)
+            .hint_text("Website (optional)...")
+            .desired_width(f32::INFINITY)
+            .margin(8)
+            .show(ui);
+
+        egui::TextEdit::singleline(&mut *edit.totp_field)
+            .char_limit(TEXT_LIMIT)
+            .password(true)
+            .hint_text("Authenticator secret or otpauth:// link (optional)...")
+            .desired_width(f32::INFINITY)
+            .margin(8)
+            .show(ui);
+
+        egui::TextEdit::multiline(&mut *edit.notes_field)
+            .char_limit(TEXT_LIMIT)
+            .hint_text("Notes...")
+            .desired_width(f32::INFINITY)
+            .desired_rows(3)
+            .margin(8)
+            .show(ui);
+
+        let mut remove = None;
+        for (i, field) in edit.fields.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt(("field kind", i))
+                    .selected_text(field.kind.label())
+                    .show_ui(ui, |ui| {
+                        for kind in FieldKind::ALL {
+                            ui.selectable_value(&mut field.kind, kind, kind.label());
+                        }
+                    });
+
+                egui::TextEdit::singleline(&mut field.name)
+                    .hint_text("Field...")
+                    .desired_width(80.0)
+                    .margin(8)
+                    .show(ui);
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
+                    let icon = egui::Image::new(egui::include_image!("../assets/trash.svg"))
+                        .fit_to_exact_size(egui::Vec2::new(14.0, 14.0));
+                    let button = egui::Button::new(icon)
+                        .corner_radius(15)
+                        .fill(egui::Color32::from_gray(10))
+                        .sense(egui::Sense::CLICK);
+                    if ui.add(button).clicked() {
+                        remove = Some(i);
+                    }
+
+                    egui::TextEdit::singleline(&mut *field.value)
+                        .char_limit(TEXT_LIMIT)
+                        .password(field.kind == FieldKind::Hidden)
+                        .hint_text("Value...")
+                        .desired_width(f32::INFINITY)
+                        .margin(8)
+                        .show(ui);
+                });
+            });
+        }
+        if let Some(i) = remove {
+            edit.fields.remove(i);
+        }
+
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
+            if ui.button("Save").clicked()
+                && let Err(error) = self.save_edit()
+            {
+                self.toast(error, ui.ctx());
+            }
+
+            if ui.button("Cancel").clicked() {
+                self.edit = None;
+            }
+
+            if ui.button("Add Field").clicked()
+                && let Some(edit) = &mut self.edit
+            {
+                edit.fields.push(EditField {
+                    kind: FieldKind::Text,
+                    name: String::new(),
+                    value: Secret::text(),
+                });
+            }
+        });
+    }
+
+    /// Live authenticator code of an entry, copied when clicked, and its countdown
+    fn totp(&mut self, password: &Password, ui: &mut egui::Ui) {
+        if !self.totps.contains_key(&password.name) {
+            let totp = self.decrypt_totp(password);
+            self.totps.insert(password.name.clone(), totp);
+        }
+
+        let totp = match &self.totps[&password.name] {
+            Ok(Some(totp)) => totp,
+            Ok(None) => return,
+            Err(error) => {
+                ui.colored_label(ui.visuals().error_fg_color, "2FA")
+                    .on_hover_text(error.to_string());
+                return;
+            }
+        };
+
+        let time = crate::now().max(0) as u64;
+        let code = totp.code(time);
+        let remaining = totp.remaining(time);
+
+        let button = egui::Button::new(egui::RichText::new(code.as_str()).monospace())
+            .corner_radius(15)
+            .fill(egui::Color32::from_gray(10));
+        let hover = match totp.label() {
+            Some(label) => format!("Copy the code for {label}"),
+            None => "Copy code".to_string(),
+        };
+        if ui.add(button).on_hover_text(hover).clicked()
+            && let Err(error) = self.copy(&password.name, &code, ui.ctx())
+        {
+            self.toast(error, ui.ctx());
+        }
+        ui.label(format!("{remaining}s"))
+            .on_hover_text("Time until the code changes");
+
+        ui.ctx()
+            .request_repaint_after(std::time::Duration::from_secs(1));
+    }
+
+    /// Website, notes, custom fields and times of the entry, below its row
+    fn detail(&mut self, password: &Password, ui: &mut egui::Ui) {
+        let Some(detail) = &mut self.detail else {
+            return;
+        };
+
+        let mut copy = None;
+        egui::Frame::new()
+            .fill(egui::Color32::from_gray(10))
+            .corner_radius(12)
+            .inner_margin(8)
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+
+                if !password.url.is_empty() {
+                    ui.hyperlink(&password.url);
+                }
+
+                if !detail.notes.is_empty() {
+                    ui.label(detail.notes.as_str());
+                }
+
+                for (i, (field, (value, revealed))) in
+                    password.fields.iter().zip(&mut detail.fields).enumerate()
+                {
+                    ui.horizontal(|ui| {
+                        ui.label(egui::RichText::new(&field.name).strong());
+
+                        match field.kind {
+                            FieldKind::Text => ui.label(value.as_str()),
+                            FieldKind::Url => ui.hyperlink(value.as_str()),
+                            FieldKind::Hidden if *revealed => {
+                                ui.label(egui::RichText::new(value.as_str()).monospace())
+                            }
+                            FieldKind::Hidden => ui.label("••••••••"),
+                        };
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
+                            let icon =
+                                egui::Image::new(egui::include_image!("../assets/clipboard.svg"))
+                                    .fit_to_exact_size(egui::Vec2::new(14.0, 14.0));
+                            let button = egui::Button::new(icon)
+                                .corner_radius(15)
+                                .fill(egui::Color32::from_gray(16))
+                                .sense(egui::Sense::CLICK);
+                            if ui.add(button).clicked() {
+                                copy = Some(i);
+                            }
+
+                            if field.kind == FieldKind::Hidden {
+                                let label = if *revealed { "Hide" } else { "Show" };
+                                if ui.button(label).clicked() {
+                                    *revealed = !*revealed;
+                                }
+                            }
+                        });
+                    });
+                }
+
+                let created = password.created.map_or("unknown".into(), ago);
+                let modified = password.modified.map_or("unknown".into(), ago);
+                let last_used = password.last_used.map_or("never".into(), ago);
+                ui.weak(format!(
+                    "Created {created}, modified {modified}, last used {last_used}"
+                ));
+            });
+
+        if let Some(i) = copy
+            && let Some(detail) = &self.detail
+        {
+            let value = Secret::new(detail.fields[i].0.to_string());
+            if let Err(error) = self.copy(&password.name, &value, ui.ctx()) {
+                self.toast(error, ui.ctx());
+            }
+        }
+    }
+}
+
+/// Rough time since a unix time, like "3 days ago"
+fn ago(time: i64) -> String {
+    let seconds = (crate::now() - time).max(0);
+
+    let (count, unit) = match seconds {
+        0..60 => return "just now".into(),
+        60..3600 => (seconds / 60, "minute"),
+        3600..86400 => (seconds / 3600, "hour"),
+        86400..2592000 => (seconds / 86400, "day"),
+        2592000..31536000 => (seconds / 2592000, "month"),
+        _ => (seconds / 31536000, "year"),
+    };
+
+    if count == 1 {
+        format!("1 {unit} ago")
+    } else {
+        format!("{count} {unit}s ago")
+    }
 }
 
-pub fn login(master: &mut String, ui: &mut egui::Ui) -> Option<String> {
-    let mut login = None;
+/// New master password and its confirmation, returns whether they are valid
+fn new_master(form: &mut ChangeMaster, ui: &mut egui::Ui) -> bool {
+    egui::TextEdit::singleline(&mut *form.new_field)
+        .char_limit(TEXT_LIMIT)
+        .password(true)
+        .hint_text("New Master Password...")
+        .desired_width(f32::INFINITY)
+        .margin(8)
+        .show(ui);
+
+    let confirmed = confirm_master(
+        &form.new_field,
+        &mut form.confirm_field,
+        "Confirm New Master Password...",
+        ui,
+    );
+
+    // validation
+    // the new master password must be between 1 and 40 characters, and confirmed
+    !form.new_field.is_empty() && form.new_field.len() <= 40 && confirmed
+}
+
+/// Field to type a new master password again, returns whether both match
+fn confirm_master(
+    master: &str,
+    confirm: &mut Secret<String>,
+    hint: &str,
+    ui: &mut egui::Ui,
+) -> bool {
+    egui::TextEdit::singleline(&mut **confirm)
+        .char_limit(TEXT_LIMIT)
+        .password(true)
+        .hint_text(hint)
+        .desired_width(f32::INFINITY)
+        .margin(8)
+        .show(ui);
+
+    if !confirm.is_empty() && master != confirm.as_str() {
+        ui.colored_label(ui.visuals().error_fg_color, "The passwords don't match");
+    }
+
+    master == confirm.as_str()
+}
+
+/// Returns whether the user asked to log in to the chosen vault with `master`, which
+/// is a recovery code if `recovery` is set. A new vault is created, once its master
+/// password is confirmed.
+pub fn login(form: &mut Login, ui: &mut egui::Ui) -> bool {
+    let mut login = false;
 
     ui.vertical_centered(|ui| {
         ui.set_width(ui.available_width() - 8.0);
 
         ui.heading("GoonPass");
+
+        // the key file belongs to the vault, forget it when switching
+        if self::vault(&mut form.vault, &form.recent.vaults, form.is_new, ui) {
+            form.key_file = None;
+            form.is_new = crate::is_new(&form.vault);
+        }
         ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
-            if ui.button("Log In").clicked() && !master.is_empty() {
-                login = Some(master.clone());
+            if ui.button("Log In").clicked() && !form.master.is_empty() {
+                login = true;
             }
 
-            egui::TextEdit::singleline(master)
-                .password(true)
-                .hint_text("Master Password...")
+            let hint = if form.recovery {
+                "Recovery Code..."
+            } else {
+                "Master Password..."
+            };
+            egui::TextEdit::singleline(&mut *form.master)
+                .char_limit(TEXT_LIMIT)
+                .password(!form.recovery)
+                .hint_text(hint)
                 .margin(8)
                 .desired_width(f32::INFINITY)
                 .show(ui);
         });
+
+        if form.is_new && !form.recovery {
+            let confirmed = confirm_master(
+                &form.master,
+                &mut form.confirm,
+                "Confirm Master Password...",
+                ui,
+            );
+            login &= confirmed;
+        }
+
+        ui.checkbox(&mut form.recovery, "Use recovery code");
+
+        if !form.recovery {
+            // a key file can only be generated along with a new vault
+            self::key_file(&mut form.key_file, form.is_new, ui);
+        }
+
+        if let Some(error) = &form.error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
     });
 
     login
 }
+
+/// Vault picker of the login screen, returns whether another vault was chosen
+fn vault(vault: &mut PathBuf, recent: &[PathBuf], is_new: bool, ui: &mut egui::Ui) -> bool {
+    let previous = vault.clone();
+
+    ui.horizontal(|ui| {
+        ui.label("Vault:");
+
+        let name = |path: &Path| {
+            path.file_name().map_or_else(
+                || path.display().to_string(),
+                |name| name.to_string_lossy().into(),
+            )
+        };
+        let selected = if is_new {
+            format!("{} (new)", name(vault))
+        } else {
+            name(vault)
+        };
+
+        egui::ComboBox::from_id_salt("vault")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                for path in recent {
+                    ui.selectable_value(vault, path.clone(), name(path))
+                        .on_hover_text(path.display().to_string());
+                }
+            })
+            .response
+            .on_hover_text(vault.display().to_string());
+
+        if ui.button("Open...").clicked()
+            && let Some(path) = rfd::FileDialog::new().pick_file()
+        {
+            *vault = path;
+        }
+
+        if ui
+            .button("New...")
+            .on_hover_text("Create a vault, it is written when you first log in")
+            .clicked()
+            && let Some(path) = rfd::FileDialog::new()
+                .set_file_name("passwords.db")
+                .save_file()
+        {
+            *vault = path;
+        }
+    });
+
+    *vault != previous
+}
+
+/// Key file picker of the login screen, offering to generate one for a new vault
+fn key_file(key_file: &mut Option<keyfile::KeyFile>, is_new: bool, ui: &mut egui::Ui) {
+    ui.horizontal(|ui| {
+        ui.label("Key file:");
+
+        let name = key_file
+            .as_ref()
+            .and_then(|key_file| key_file.path().file_name())
+            .map_or("None".into(), |name| name.to_string_lossy());
+        match key_file {
+            Some(keyfile::KeyFile::Generate(_)) => ui.label(format!("{name} (new)")),
+            _ => ui.label(name),
+        };
+
+        if ui.button("Browse...").clicked()
+            && let Some(path) = rfd::FileDialog::new().pick_file()
+        {
+            *key_file = Some(keyfile::KeyFile::Open(path));
+        }
+
+        if is_new
+            && ui
+                .button("Generate...")
+                .on_hover_text("Create a new key file along with the vault")
+                .clicked()
+            && let Some(path) = rfd::FileDialog::new()
+                .set_file_name("goonpass.key")
+                .save_file()
+        {
+            *key_file = Some(keyfile::KeyFile::Generate(path));
+        }
+
+        if key_file.is_some() && ui.button("Clear").clicked() {
+            *key_file = None;
+        }
+    });
+}